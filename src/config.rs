@@ -0,0 +1,77 @@
+//! Defaults from a `.grelly.toml` file.
+//!
+//! ```toml
+//! tag_format = "v{major}.{minor}.{patch}"
+//! tag_message_format = "Release {major}.{minor}.{patch}"
+//! release_prefixes = ["release:", "chore(release):"]
+//! main_branches = ["main", "trunk", "develop"]
+//!
+//! [branch_prefixes]
+//! "feat/" = "feature"
+//! "hotfix/" = "fix"
+//!
+//! [author]
+//! name = "Release Bot"
+//! email = "release@example.com"
+//! ```
+//!
+//! Every value is optional. Command line flags override the file,
+//! the file overrides the built-in defaults.
+
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{BranchCategory, VersionError};
+
+/// Name of the config file
+pub const CONFIG_FILE: &str = ".grelly.toml";
+
+/// The contents of a `.grelly.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub tag_format: Option<String>,
+    pub tag_message_format: Option<String>,
+    pub release_prefixes: Option<Vec<String>>,
+    pub branch_prefixes: Option<BTreeMap<String, BranchCategory>>,
+    pub main_branches: Option<Vec<String>>,
+    #[serde(default)]
+    pub author: Author,
+}
+
+/// Identity for release commits and tags
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Author {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl Config {
+    /// Parse the given config file
+    pub fn load(path: &Path) -> Result<Self, VersionError> {
+        let text = fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+
+    /// Look for a `.grelly.toml` in start and each of its parents,
+    /// the first one found wins. Returns the default config if
+    /// there is none.
+    pub fn discover(start: &Path) -> Result<Self, VersionError> {
+        match find_config(start) {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+fn find_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
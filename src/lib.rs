@@ -0,0 +1,2421 @@
+//! Generate semantic versions for git repositories.
+//!
+//! The major version comes from the branch name, a version tag or a
+//! release commit (`release: 17.0.0`), the patch version is the number
+//! of commits since that release.
+
+use git2::{
+    Commit, Object, ObjectType, Oid, Repository, RepositoryOpenFlags, Signature, StatusOptions,
+};
+use log::{debug, info, trace, warn};
+use regex::{Match, Regex};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    ffi::OsStr,
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::LazyLock,
+};
+use thiserror::Error;
+
+pub mod config;
+
+#[derive(Error, Debug)]
+pub enum VersionError {
+    #[error("Error: {0}")]
+    Generic(String),
+    #[error("git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("not a git repository: {0}")]
+    NotARepository(PathBuf),
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+    #[error("config error: {0}")]
+    Config(#[from] toml::de::Error),
+    #[error("branch says {component}={branch} but HEAD history says {component}={head} (branch version {branch_version}, HEAD version {head_version})")]
+    Mismatch {
+        component: &'static str,
+        branch: usize,
+        head: usize,
+        branch_version: String,
+        head_version: String,
+    },
+}
+
+impl VersionError {
+    /// The code of the wrapped git error, None for other errors
+    pub fn git_code(&self) -> Option<git2::ErrorCode> {
+        match self {
+            VersionError::Git(e) => Some(e.code()),
+            _ => None,
+        }
+    }
+
+    /// The class (the git subsystem) of the wrapped git error, None
+    /// for other errors
+    pub fn git_class(&self) -> Option<git2::ErrorClass> {
+        match self {
+            VersionError::Git(e) => Some(e.class()),
+            _ => None,
+        }
+    }
+
+    /// True if a git object, reference or file does not exist
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            VersionError::Git(e) => e.code() == git2::ErrorCode::NotFound,
+            VersionError::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+
+    /// True if a git object, reference or file already exists
+    pub fn is_exists(&self) -> bool {
+        match self {
+            VersionError::Git(e) => e.code() == git2::ErrorCode::Exists,
+            VersionError::Io(e) => e.kind() == std::io::ErrorKind::AlreadyExists,
+            _ => false,
+        }
+    }
+
+    /// True if a file could not be read or written for lack of
+    /// permission, or git was denied access to a remote
+    pub fn is_permission_denied(&self) -> bool {
+        match self {
+            VersionError::Git(e) => e.code() == git2::ErrorCode::Auth,
+            VersionError::Io(e) => e.kind() == std::io::ErrorKind::PermissionDenied,
+            _ => false,
+        }
+    }
+}
+
+impl From<&str> for VersionError {
+    fn from(s: &str) -> Self {
+        VersionError::Generic(s.to_string())
+    }
+}
+
+/// Default prefix of release commit messages
+pub const DEFAULT_RELEASE_PREFIX: &str = "release:";
+
+/// Prefix of GitFlow style release branches (release/1.2)
+pub const RELEASE_BRANCH_PREFIX: &str = "release/";
+
+/// Notes ref whose note on a commit pins the version of the commit
+pub const NOTES_REF: &str = "refs/notes/grelly";
+
+/// Default separators between the numbers of a version (1.2.3, 1-2-3)
+pub const DEFAULT_SEPARATORS: &str = ".-";
+
+/// Default number of commits searched for a release
+pub const DEFAULT_MAX_DEPTH: usize = 4096;
+
+/// What kind of work a branch prefix stands for
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchCategory {
+    Feature,
+    Fix,
+}
+
+/// The branch prefixes known without configuration
+pub fn default_branch_prefixes() -> Vec<(String, BranchCategory)> {
+    vec![
+        (String::from("feature/"), BranchCategory::Feature),
+        (String::from("fix/"), BranchCategory::Fix),
+    ]
+}
+
+/// The branches with the plain head version if not configured
+pub fn default_main_branches() -> Vec<String> {
+    vec![
+        String::from("master"),
+        String::from("main"),
+        String::from("release"),
+    ]
+}
+
+/// Open the repository at path, searching parent directories like
+/// git does and honouring GIT_DIR and GIT_WORK_TREE. With bare the
+/// repository is opened without a working directory, work_tree
+/// points the working directory somewhere else than the git dir.
+pub fn open_repository(
+    path: &Path,
+    bare: bool,
+    work_tree: Option<&Path>,
+) -> Result<Repository, VersionError> {
+    let mut flags = RepositoryOpenFlags::FROM_ENV;
+    if bare {
+        flags |= RepositoryOpenFlags::BARE;
+    }
+
+    let repo = Repository::open_ext(path, flags, std::iter::empty::<&OsStr>()).map_err(|e| {
+        if e.code() == git2::ErrorCode::NotFound {
+            VersionError::NotARepository(path.to_path_buf())
+        } else {
+            VersionError::Git(e)
+        }
+    })?;
+    if let Some(work_tree) = work_tree {
+        repo.set_workdir(work_tree, false)?;
+    }
+    Ok(repo)
+}
+
+/// Open the repository of the submodule with the given name or path
+pub fn open_submodule(repo: &Repository, name: &str) -> Result<Repository, VersionError> {
+    let submodule = repo
+        .find_submodule(name)
+        .map_err(|_| VersionError::Generic(format!("no submodule {}", name)))?;
+    submodule.open().map_err(|_| {
+        VersionError::Generic(format!(
+            "submodule {} is not initialized, run git submodule update --init {}",
+            name,
+            submodule.path().display()
+        ))
+    })
+}
+
+/// Which commits count towards the patch distance
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CountMode {
+    /// only the first-parent line, a merge counts as one commit
+    #[default]
+    FirstParent,
+    /// every commit, including those on merged branches
+    All,
+}
+
+/// Which release wins if a commit has both a release message and a
+/// version tag
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prefer {
+    /// the version in the release commit message
+    #[default]
+    Commit,
+    /// the version tag
+    Tag,
+}
+
+/// Settings for resolving the version of a commit
+#[derive(Debug, Clone)]
+pub struct ResolveOptions {
+    /// read version components with leading zeros (v01.02.03) instead
+    /// of ignoring them as malformed
+    pub leading_zeros: bool,
+    /// characters accepted between the numbers of a version
+    pub separators: Separators,
+    /// revision to compute the version of instead of HEAD
+    pub rev: Option<String>,
+    /// which commits are walked and counted
+    pub count_mode: CountMode,
+    /// leave merge commits out of the patch distance
+    pub skip_merges: bool,
+    /// search at most this many commits for a release, 0 is unlimited;
+    /// if none is found the initial version plus the distance walked is used
+    pub max_depth: usize,
+    /// unix time the walk stops at, older commits are treated as if
+    /// the history ended before them
+    pub since: Option<i64>,
+    /// resolve and parse at most this many tags, 0 is unlimited. The
+    /// walk resolves tags as it reaches their commits, nearest first;
+    /// once the limit is reached only release commits count, a release
+    /// marked by a tag further back is missed. The tag refs themselves
+    /// are still all read up front to know which commits are tagged.
+    pub max_tag_scan: usize,
+    /// only tags starting with this are releases, the prefix is
+    /// stripped before the name is parsed as a version
+    pub tag_prefix: Option<String>,
+    /// tags whose name matches one of these globs (`*` any text, `?`
+    /// one character) are never releases, as in `ci-*`
+    pub ignore_tags: Vec<String>,
+    /// fail instead of warning if a tag points to an object missing
+    /// from the repository, as in a shallow clone
+    pub require_tags: bool,
+    /// use this tag as the release instead of searching the history
+    pub from_tag: Option<String>,
+    /// read the version from the message of annotated tags whose
+    /// name is no version (tag release-2024, message Version 1.4.0)
+    pub tag_message_version: bool,
+    /// if the first-parent line has no release within max_depth,
+    /// walk all of the history (without a depth limit) for the
+    /// nearest release on a merged branch; the distance then counts
+    /// every commit walked. Slow in large histories without releases.
+    pub deep_search: bool,
+    /// baseline if no release is found, 0.0.0 if None
+    pub initial_version: Option<SemanticVersion>,
+    /// source of the version if a commit is a release by message and tag
+    pub prefer: Prefer,
+    /// minimum length of the abbreviated commit id, git's own
+    /// abbreviation if None
+    pub short_len: Option<usize>,
+    /// use the full 40 character commit id instead of an abbreviation
+    pub full_hash: bool,
+    /// render the commit as build metadata (1.2.3+a1b2c3d)
+    pub build_metadata: bool,
+    /// appended to the ident if the working tree has uncommitted
+    /// changes (empty = no check)
+    pub dirty_suffix: String,
+    /// use a slug of the branch name (team-foo for feature/Team/foo)
+    /// as the ident of feature and fix branches
+    pub slug_branch: bool,
+    /// append the distance to the release to the ident of feature
+    /// and fix branches (1.2.3-foo.4)
+    pub pre_counter: bool,
+    /// fail if the resolved major.minor is below the highest version
+    /// tag reachable from the start commit, a sign that branch and
+    /// tags disagree
+    pub strict: bool,
+    /// pre-release ident that replaces whatever the branch implies
+    pub pre: Option<String>,
+    /// a commit whose message starts with one of these (ignoring
+    /// case) is a release commit, the first one is used for new releases
+    pub release_prefixes: Vec<String>,
+    /// branch name prefixes and the category they mark, the
+    /// longest matching prefix wins
+    pub branch_prefixes: Vec<(String, BranchCategory)>,
+    /// branches that get the version of their head, without ident
+    pub main_branches: Vec<String>,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            leading_zeros: false,
+            separators: Separators::default(),
+            rev: None,
+            count_mode: CountMode::default(),
+            skip_merges: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            since: None,
+            max_tag_scan: 0,
+            tag_prefix: None,
+            ignore_tags: Vec::new(),
+            require_tags: false,
+            from_tag: None,
+            tag_message_version: false,
+            deep_search: false,
+            initial_version: None,
+            prefer: Prefer::default(),
+            short_len: None,
+            full_hash: false,
+            build_metadata: true,
+            dirty_suffix: String::new(),
+            slug_branch: false,
+            pre_counter: true,
+            strict: false,
+            pre: None,
+            release_prefixes: vec![String::from(DEFAULT_RELEASE_PREFIX)],
+            branch_prefixes: default_branch_prefixes(),
+            main_branches: default_main_branches(),
+        }
+    }
+}
+
+impl ResolveOptions {
+    /// True if tag name is a candidate release: it has the tag prefix
+    /// and matches none of the ignored globs
+    fn accepts_tag(&self, name: &str) -> bool {
+        let has_prefix = match self.tag_prefix {
+            Some(ref prefix) => name.starts_with(prefix.as_str()),
+            None => true,
+        };
+        has_prefix && !self.ignore_tags.iter().any(|glob| glob_match(glob, name))
+    }
+
+    /// The text after the release prefix if message is a release commit
+    fn strip_release_prefix<'m>(&self, message: &'m str) -> Option<&'m str> {
+        self.release_prefixes.iter().find_map(|p| {
+            message
+                .get(..p.len())
+                .filter(|head| head.eq_ignore_ascii_case(p))
+                .map(|_| &message[p.len()..])
+        })
+    }
+}
+
+/// What the branch-name tells us about the version
+#[derive(Debug)]
+pub enum BranchVersion {
+    // master, main, release
+    Master,
+    // release: 1.2.3
+    Release(SemanticVersion),
+    // feature/myfeature
+    Feature(String),
+    // fix/myfix
+    Fix(String),
+    // other
+    Other(String),
+    // HEAD is not on a branch, e.g. a CI checkout of a commit
+    Detached,
+}
+
+impl BranchVersion {
+    /// Name of the kind of branch, `master`, `release`, `feature`,
+    /// `fix`, `other` or `detached`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BranchVersion::Master => "master",
+            BranchVersion::Release(_) => "release",
+            BranchVersion::Feature(_) => "feature",
+            BranchVersion::Fix(_) => "fix",
+            BranchVersion::Other(_) => "other",
+            BranchVersion::Detached => "detached",
+        }
+    }
+}
+
+/// True if HEAD points to a branch without commits, as in a new
+/// repository
+fn head_is_unborn(repo: &Repository) -> bool {
+    matches!(repo.head(), Err(ref e) if e.code() == git2::ErrorCode::UnbornBranch)
+}
+
+/// takes a repository and returns the branch name
+/// if the repository is a git repository, otherwise returns Error.
+/// With options.rev the branch is the one rev names, a rev that is
+/// no local branch (a commit, a tag, HEAD~1) is detached.
+pub fn branch_version(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<BranchVersion, VersionError> {
+    let branch = match options.rev {
+        Some(ref rev) => match repo.find_branch(rev, git2::BranchType::Local) {
+            Ok(branch) => match branch.name()? {
+                Some(name) => name.to_lowercase(),
+                None => return Ok(BranchVersion::Detached),
+            },
+            Err(_) => return Ok(BranchVersion::Detached),
+        },
+        None => {
+            if repo.head_detached()? {
+                return Ok(BranchVersion::Detached);
+            }
+            if head_is_unborn(repo) {
+                return Ok(BranchVersion::Master);
+            }
+            let head = repo.head()?;
+            match head.shorthand() {
+                Some(name) => name.to_lowercase(),
+                None => return Ok(BranchVersion::Detached),
+            }
+        }
+    };
+
+    // GitFlow release branches (release/1.2) name the version after
+    // the prefix, missing components are zero
+    let version_name = branch
+        .strip_prefix(RELEASE_BRANCH_PREFIX)
+        .unwrap_or(&branch);
+
+    match version_from_string(version_name, None, options) {
+        Some(v) => Ok(BranchVersion::Release(v)),
+        None => {
+            // strip_prefix is safe for any prefix and branch name,
+            // a byte offset could split a multibyte character
+            let category = options
+                .branch_prefixes
+                .iter()
+                .filter_map(|(prefix, category)| {
+                    let prefix = prefix.to_lowercase();
+                    branch
+                        .strip_prefix(prefix.as_str())
+                        .map(|rest| (prefix.len(), *category, rest))
+                })
+                .max_by_key(|(len, _, _)| *len);
+
+            if options
+                .main_branches
+                .iter()
+                .any(|name| name.to_lowercase() == branch)
+            {
+                Ok(BranchVersion::Master)
+            } else if let Some((_, category, rest)) = category {
+                match category {
+                    BranchCategory::Feature => Ok(BranchVersion::Feature(rest.to_string())),
+                    BranchCategory::Fix => Ok(BranchVersion::Fix(rest.to_string())),
+                }
+            } else {
+                Ok(BranchVersion::Other(branch.to_string()))
+            }
+        }
+    }
+}
+
+/// a major.minor.patch version
+#[derive(Debug, Clone, Serialize)]
+pub struct SemanticVersion {
+    pub major: usize,
+    pub minor: usize,
+    pub patch: usize,
+    pub ident: Option<String>,
+    pub commit: Option<String>,
+    /// build metadata, rendered after a `+`
+    pub build: Option<String>,
+    /// commits between the release and this version, already
+    /// included in patch; None if not computed from a history
+    pub distance: Option<usize>,
+}
+
+impl SemanticVersion {
+    pub fn new(
+        major: usize,
+        minor: usize,
+        patch: usize,
+        ident: Option<String>,
+        commit: Option<String>,
+        build: Option<String>,
+    ) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            ident: clean_ident(ident),
+            commit,
+            build,
+            distance: None,
+        }
+    }
+
+    // fn from_triple(major: usize, minor: usize, patch: usize) -> Self {
+    //     SemanticVersion::new(major, minor, patch, None, None, None)
+    // }
+
+    #[deprecated(note = "use the Display impl, e.g. to_string()")]
+    pub fn version_string(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn panoo_string(&self) -> String {
+        let mut version = format!("P{}-{}", self.major, self.minor);
+        if self.patch > 0 {
+            version.push_str(&format!("-{}", self.patch));
+        }
+        if let Some(ref v) = self.ident {
+            version.push_str(&format!("-{}", v));
+        }
+        version
+    }
+
+    /// The version as a [`semver::Version`], with the ident as
+    /// pre-release and the build metadata as build, sanitized as
+    /// Display renders them, so both print the same
+    pub fn to_semver(&self) -> semver::Version {
+        let mut version =
+            semver::Version::new(self.major as u64, self.minor as u64, self.patch as u64);
+        if let Some(ref ident) = self.ident {
+            // sanitized idents and builds are always valid
+            version.pre = semver::Prerelease::new(&sanitize_ident(ident)).unwrap_or_default();
+        }
+        if let Some(ref build) = self.build {
+            version.build = semver::BuildMetadata::new(&sanitize_build(build)).unwrap_or_default();
+        }
+        version
+    }
+
+    /// The version in PEP 440 form for Python packages: alpha, beta
+    /// and rc idents become a1, b2, rc1 (a missing number is 1), a
+    /// distance to the release becomes .devN and any other ident a
+    /// local version label (1.2.3+login).
+    pub fn pep440_string(&self) -> String {
+        let mut version = format!("{}.{}.{}", self.major, self.minor, self.patch);
+        let mut local = None;
+        if let Some(ref ident) = self.ident {
+            match pep440_pre(ident) {
+                Some(pre) => version.push_str(&pre),
+                None => local = Some(ident),
+            }
+        }
+        if let Some(distance) = self.distance.filter(|d| *d > 0) {
+            version.push_str(&format!(".dev{}", distance));
+        }
+        if let Some(ident) = local {
+            let label: String = ident
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '.' })
+                .collect();
+            version.push('+');
+            version.push_str(&label.to_lowercase());
+        }
+        version
+    }
+}
+
+/// a1, b2 or rc3 for a SemVer pre-release ident like alpha.1, beta2
+/// or rc-3; None if the ident is no pre-release PEP 440 knows
+fn pep440_pre(ident: &str) -> Option<String> {
+    let ident = ident.to_lowercase();
+    let split = ident
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(ident.len());
+    let (phase, number) = ident.split_at(split);
+    let phase = match phase {
+        "a" | "alpha" => "a",
+        "b" | "beta" => "b",
+        "c" | "rc" | "pre" | "preview" => "rc",
+        _ => return None,
+    };
+    let number = number.trim_start_matches(['.', '-', '_']);
+    let number = if number.is_empty() {
+        1
+    } else {
+        number.parse::<usize>().ok()?
+    };
+    Some(format!("{}{}", phase, number))
+}
+
+/// `major.minor.patch[-ident][+build]`
+impl fmt::Display for SemanticVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(ref v) = self.ident {
+            let ident = sanitize_ident(v);
+            if !ident.is_empty() {
+                write!(f, "-{}", ident)?;
+            }
+        }
+        if let Some(ref b) = self.build {
+            let build = sanitize_build(b);
+            if !build.is_empty() {
+                write!(f, "+{}", build)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Version of the JSON document of [`render_json`], raised on
+/// incompatible changes
+pub const JSON_SCHEMA: u32 = 1;
+
+/// How [`render`] prints a version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionFormat {
+    /// 1.2.3-ident+build
+    Plain,
+    /// {"schema":1,"version":"1.2.3","major":1,...}
+    Json,
+    /// 1.2.3rc1 or 1.2.3.dev4 for Python packages (PEP 440)
+    Pep440,
+    /// Docker image tags, one per line: 1.2.3, 1.2 and 1; only
+    /// 1.2.3-ident for pre-releases. Never `latest`, which depends on
+    /// the branch, see [`docker_tags`] for that.
+    Docker,
+}
+
+/// The version in the given format. A Result because serializing
+/// [`VersionFormat::Json`] can fail, the other formats never do.
+pub fn render(version: &SemanticVersion, format: VersionFormat) -> Result<String, VersionError> {
+    Ok(match format {
+        VersionFormat::Plain => version.to_string(),
+        VersionFormat::Json => render_json(version, false)?,
+        VersionFormat::Pep440 => version.pep440_string(),
+        VersionFormat::Docker => docker_tags(version, false).join("\n"),
+    })
+}
+
+/// The JSON document of [`VersionFormat::Json`]
+#[derive(Serialize)]
+struct JsonVersion<'a> {
+    schema: u32,
+    version: String,
+    #[serde(flatten)]
+    semver: &'a SemanticVersion,
+}
+
+/// The version as a JSON document, indented with pretty
+pub fn render_json(version: &SemanticVersion, pretty: bool) -> Result<String, VersionError> {
+    let doc = JsonVersion {
+        schema: JSON_SCHEMA,
+        version: version.to_string(),
+        semver: version,
+    };
+    let json = if pretty {
+        serde_json::to_string_pretty(&doc)
+    } else {
+        serde_json::to_string(&doc)
+    };
+    json.map_err(|e| VersionError::Generic(e.to_string()))
+}
+
+/// Image tags for version, a `+` is not allowed in docker tags so the
+/// build metadata is left out. With latest a release also gets the
+/// `latest` tag.
+pub fn docker_tags(version: &SemanticVersion, latest: bool) -> Vec<String> {
+    let full = format!("{}.{}.{}", version.major, version.minor, version.patch);
+    match version.ident {
+        // a pre-release must not move the 1.2 or latest tags
+        Some(ref ident) => vec![format!("{}-{}", full, sanitize_ident(ident))],
+        None => {
+            let mut tags = vec![
+                full,
+                format!("{}.{}", version.major, version.minor),
+                version.major.to_string(),
+            ];
+            if latest {
+                tags.push(String::from("latest"));
+            }
+            tags
+        }
+    }
+}
+
+/// A full SemVer version, `major.minor.patch[-ident][+build]`. Stricter
+/// than [`parse_semver`]: no `v` prefix, no missing components.
+static STRICT_VERSION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(0|[1-9]\d*)\.(0|[1-9]\d*)\.(0|[1-9]\d*)(?:-((?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?(?:\+([0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?$",
+    )
+    .expect("the strict version pattern is valid")
+});
+
+/// Parses a full version like `1.2.3-rc.1+build.5`, see
+/// [`parse_semver`] for the lenient form that also takes `v1.2`.
+impl SemanticVersion {
+    /// Parse s strictly as [`FromStr`] does or, with lenient, as
+    /// [`parse_semver`] does: a `v` prefix and missing components
+    /// are accepted, `v1.2` is 1.2.0.
+    pub fn parse(s: &str, lenient: bool) -> Result<Self, VersionError> {
+        if !lenient {
+            return s.parse();
+        }
+        parse_semver(s).ok_or_else(|| VersionError::Generic(format!("'{}' is not a version", s)))
+    }
+}
+
+impl FromStr for SemanticVersion {
+    type Err = VersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            VersionError::Generic(format!(
+                "'{}' is not a version (major.minor.patch[-ident][+build])",
+                s
+            ))
+        };
+        let caps = STRICT_VERSION_RE.captures(s).ok_or_else(invalid)?;
+        let number = |i: usize| caps[i].parse::<usize>().map_err(|_| invalid());
+        Ok(SemanticVersion::new(
+            number(1)?,
+            number(2)?,
+            number(3)?,
+            caps.get(4).map(|m| m.as_str().to_string()),
+            None,
+            caps.get(5).map(|m| m.as_str().to_string()),
+        ))
+    }
+}
+
+/// Versions are equal if they have the same precedence,
+/// commit and build metadata are ignored.
+impl PartialEq for SemanticVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SemanticVersion {}
+
+impl PartialOrd for SemanticVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// SemVer precedence: major, minor and patch compare numerically,
+/// a pre-release (ident) is lower than the release itself.
+impl Ord for SemanticVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.major
+            .cmp(&other.major)
+            .then(self.minor.cmp(&other.minor))
+            .then(self.patch.cmp(&other.patch))
+            .then_with(|| match (&self.ident, &other.ident) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => ident_cmp(a, b),
+            })
+    }
+}
+
+/// Compare two pre-release idents field by field: numeric fields
+/// numerically and lower than alphanumeric ones, a shorter ident
+/// is lower if all shared fields are equal.
+fn ident_cmp(a: &str, b: &str) -> Ordering {
+    let mut afields = a.split('.');
+    let mut bfields = b.split('.');
+    loop {
+        let ord = match (afields.next(), bfields.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => x.cmp(y),
+            },
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+/// Parse a version like `1.2.3`, `v1.2`, `1-2-3` or `2.0.0-rc.1`.
+/// Missing components are zero, components with leading zeros are
+/// rejected. Needs no repository.
+pub fn parse_semver(s: &str) -> Option<SemanticVersion> {
+    parse_version(s, false, &VERSION_RE)
+}
+
+/// Compiled once, the walk parses a version for every release commit
+static VERSION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&version_pattern(DEFAULT_SEPARATORS)).expect("the version pattern is valid")
+});
+
+/// Pattern of a version whose numbers are separated by any of the
+/// characters in separators
+fn version_pattern(separators: &str) -> String {
+    let class: String = separators
+        .chars()
+        .map(|c| regex::escape(c.encode_utf8(&mut [0; 4])))
+        .collect();
+    format!(
+        r"(?i)^p?(\d+)([{0}](\d+))?([{0}](\d+))?(-([0-9a-z\-]+(\.[0-9a-z\-]+)*))?$",
+        class
+    )
+}
+
+/// The characters accepted between the numbers of a version
+/// (`.` and `-` by default, `_` for tags like `1_2_3`)
+#[derive(Debug, Clone)]
+pub struct Separators {
+    chars: String,
+    re: Regex,
+}
+
+impl Separators {
+    /// Accept any of the characters in chars as separator
+    pub fn new(chars: &str) -> Result<Self, VersionError> {
+        if chars.is_empty() || chars.chars().any(|c| c.is_ascii_alphanumeric()) {
+            return Err(VersionError::Generic(format!(
+                "'{}' is no set of separators",
+                chars
+            )));
+        }
+        let re = Regex::new(&version_pattern(chars))
+            .map_err(|e| VersionError::Generic(e.to_string()))?;
+        Ok(Self {
+            chars: chars.to_string(),
+            re,
+        })
+    }
+
+    /// The accepted characters
+    pub fn as_str(&self) -> &str {
+        &self.chars
+    }
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Self {
+            chars: DEFAULT_SEPARATORS.to_string(),
+            re: VERSION_RE.clone(),
+        }
+    }
+}
+
+fn parse_version(raw_name: &str, leading_zeros: bool, re: &Regex) -> Option<SemanticVersion> {
+    // v1.2.3 is version 1.2.3
+    let name = raw_name
+        .strip_prefix(|c| c == 'v' || c == 'V')
+        .unwrap_or(raw_name);
+
+    let caps = re.captures(name)?;
+    let major = to_number(caps.get(1), leading_zeros)?;
+    let minor = to_number(caps.get(3), leading_zeros)?;
+    let patch = to_number(caps.get(5), leading_zeros)?;
+    let ident = caps.get(7).map(|m| m.as_str().to_string());
+
+    Some(SemanticVersion::new(major, minor, patch, ident, None, None))
+}
+
+/// [`parse_semver`] with the options of the walk, commit is recorded
+/// as the commit of the version
+fn version_from_string(
+    raw_name: &str,
+    commit: Option<&Commit>,
+    options: &ResolveOptions,
+) -> Option<SemanticVersion> {
+    let mut version = parse_version(raw_name, options.leading_zeros, &options.separators.re)?;
+    version.commit = commit.and_then(|c| abbreviate(c.as_object(), options).ok());
+
+    trace!("semver: {} {:?}", raw_name, version);
+    Some(version)
+}
+
+/// A missing component is 0, a malformed one is None. SemVer forbids
+/// leading zeros, with leading_zeros they are accepted (01 is 1).
+fn to_number(s: Option<Match>, leading_zeros: bool) -> Option<usize> {
+    match s {
+        Some(s) => {
+            let digits = s.as_str();
+            if !leading_zeros && digits.len() > 1 && digits.starts_with('0') {
+                None
+            } else {
+                digits.parse::<usize>().ok()
+            }
+        }
+        None => Some(0),
+    }
+}
+
+/// a version for a commit that is a few commits (patches)
+/// away from a release version
+#[derive(Debug)]
+pub struct PatchVersion {
+    release: Option<SemanticVersion>,
+    /// number of commits between the release and the commit
+    pub patch_count: usize,
+    _patch_oid: Option<Oid>,
+    patch_short: Option<String>,
+    ident: Option<String>,
+    /// the release the version counts from, None if there is none
+    pub baseline: Option<Baseline>,
+}
+
+/// The commit a [`PatchVersion`] counts from
+#[derive(Debug)]
+pub struct Baseline {
+    pub oid: Oid,
+    /// how it was found, `tag v1.2.0` or `commit release: 1.2.0`
+    pub source: String,
+}
+
+impl PatchVersion {
+    fn new(
+        release: SemanticVersion,
+        distance: usize,
+        ident: Option<String>,
+        oid: Option<Oid>,
+        short: Option<String>,
+    ) -> Self {
+        Self {
+            release: Some(release),
+            patch_count: distance,
+            _patch_oid: oid,
+            patch_short: short,
+            ident,
+            baseline: None,
+        }
+    }
+
+    pub fn semver(&self) -> SemanticVersion {
+        let mut v = match self.release {
+            Some(ref rv) => SemanticVersion::new(
+                rv.major,
+                rv.minor,
+                rv.patch + self.patch_count,
+                self.ident.clone().or_else(|| rv.ident.clone()),
+                self.patch_short.clone(),
+                self.patch_short.clone(),
+            ),
+            None => SemanticVersion::new(
+                0,
+                0,
+                self.patch_count,
+                self.ident.clone(),
+                self.patch_short.clone(),
+                self.patch_short.clone(),
+            ),
+        };
+        v.distance = Some(self.patch_count);
+        v
+    }
+}
+
+pub fn head_version(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<PatchVersion, VersionError> {
+    let initial = options
+        .initial_version
+        .clone()
+        .unwrap_or_else(|| SemanticVersion::new(0, 0, 0, None, None, None));
+
+    // a freshly initialized repository has nothing to count
+    if options.rev.is_none() && head_is_unborn(repo) {
+        return Ok(PatchVersion::new(initial, 0, None, None, None));
+    }
+
+    let head_oid = start_commit(repo, options)?;
+    let head_short = abbreviate(&repo.find_object(head_oid, None)?, options)?;
+
+    if let Some(ref name) = options.from_tag {
+        return from_tag_version(repo, options, name, head_oid, head_short);
+    }
+
+    // names of all tags in the repository by the commit they tag
+    let tagindex = tag_index(repo, options)?;
+
+    let (release, count) = match search_release(repo, options, &tagindex)? {
+        (None, count) if options.deep_search && options.count_mode == CountMode::FirstParent => {
+            debug!("no release on the first-parent line, searching all history");
+            let all = ResolveOptions {
+                count_mode: CountMode::All,
+                max_depth: 0,
+                ..options.clone()
+            };
+            match search_release(repo, &all, &tagindex)? {
+                (Some(rv), count) => (Some(rv), count),
+                (None, _) => (None, count),
+            }
+        }
+        found => found,
+    };
+
+    let (release, baseline) = match release {
+        Some((rv, baseline)) => (rv, Some(baseline)),
+        None => (initial, None),
+    };
+    let mut version = PatchVersion::new(release, count, None, Some(head_oid), Some(head_short));
+    version.baseline = baseline;
+    Ok(version)
+}
+
+/// The nearest release reachable from the start commit as it was
+/// released, without the distance. None if the history has none.
+pub fn latest_release(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<Option<SemanticVersion>, VersionError> {
+    let head = head_version(repo, options)?;
+    Ok(head.baseline.and(head.release))
+}
+
+/// Walk the history from the start commit to the first release,
+/// returning it and the number of commits walked before it. The
+/// release is None if the walk ends or hits max_depth first.
+fn search_release(
+    repo: &Repository,
+    options: &ResolveOptions,
+    tagindex: &HashMap<Oid, Vec<String>>,
+) -> Result<(Option<(SemanticVersion, Baseline)>, usize), VersionError> {
+    let revwalk = history_walk(repo, options)?;
+
+    let mut count = 0;
+    // tags resolved so far, past max_tag_scan they are left alone
+    let mut resolved = 0;
+
+    for roid in revwalk {
+        let oid = roid?;
+
+        // find the commit
+        let commit = repo.find_commit(oid)?;
+
+        if let Some(since) = options.since {
+            if commit.time().seconds() < since {
+                debug!("reached {} older than {}", oid, iso_date(since));
+                break;
+            }
+        }
+
+        let mut tags = tagindex.get(&oid).map_or(&[][..], Vec::as_slice);
+        if options.max_tag_scan != 0 {
+            tags = &tags[..tags.len().min(options.max_tag_scan - resolved)];
+        }
+        resolved += tags.len();
+
+        if let Some((rv, source)) = release_of(repo, tags, &commit, options)? {
+            return Ok((Some((rv, Baseline { oid, source })), count));
+        }
+
+        trace!("{} {}", oid, commit.summary().unwrap_or(""));
+
+        if counts(&commit, options) {
+            count += 1;
+        }
+        if options.max_depth != 0 && count >= options.max_depth {
+            debug!("no release within {} commits", options.max_depth);
+            break;
+        }
+    }
+
+    Ok((None, count))
+}
+
+/// Whether commit adds to the patch distance, merges don't with
+/// skip_merges
+fn counts(commit: &Commit, options: &ResolveOptions) -> bool {
+    !(options.skip_merges && commit.parent_count() > 1)
+}
+
+/// Describes a release commit as a baseline
+fn commit_source(commit: &Commit) -> String {
+    format!("commit {}", commit.summary().unwrap_or(""))
+}
+
+/// The release commit has, by its message or a tag. If it has both
+/// and they disagree, options.prefer decides.
+fn release_of(
+    repo: &Repository,
+    tags: &[String],
+    commit: &Commit,
+    options: &ResolveOptions,
+) -> Result<Option<(SemanticVersion, String)>, VersionError> {
+    let commit_rv = commit_release(commit, options);
+    if let Some(ref rv) = commit_rv {
+        debug!(
+            "commit-rv: {:?} {:?} {}",
+            commit.summary().unwrap_or(""),
+            rv,
+            abbreviate(commit.as_object(), options)?
+        );
+    }
+
+    // check if there is a tag for that commit, only now the
+    // tags are resolved and parsed
+    let tag_rv = tag_release(repo, tags, commit, options);
+    if let Some((ref name, ref rv)) = tag_rv {
+        debug!("tag-rv: {:?} {:?}", name, rv);
+    }
+
+    Ok(match (commit_rv, tag_rv) {
+        (Some(crv), Some((name, trv))) => {
+            if crv != trv {
+                warn!(
+                    "commit {} is release {} by its message but tagged {}, using the {}",
+                    abbreviate(commit.as_object(), options)?,
+                    crv,
+                    name,
+                    match options.prefer {
+                        Prefer::Commit => "message",
+                        Prefer::Tag => "tag",
+                    }
+                );
+            }
+            match options.prefer {
+                Prefer::Commit => Some((crv, commit_source(commit))),
+                Prefer::Tag => Some((trv, format!("tag {}", name))),
+            }
+        }
+        (Some(crv), None) => Some((crv, commit_source(commit))),
+        (None, Some((name, trv))) => Some((trv, format!("tag {}", name))),
+        (None, None) => None,
+    })
+}
+
+/// Every release reachable from HEAD, by release commit or version
+/// tag, oldest first.
+pub fn release_history(repo: &Repository) -> Result<Vec<(Oid, SemanticVersion)>, VersionError> {
+    let options = ResolveOptions {
+        count_mode: CountMode::All,
+        max_depth: 0,
+        ..ResolveOptions::default()
+    };
+    if head_is_unborn(repo) {
+        return Ok(Vec::new());
+    }
+    let tagindex = tag_index(repo, &options)?;
+
+    let mut releases = Vec::new();
+    for oid in history_walk(repo, &options)? {
+        let oid = oid?;
+        let commit = repo.find_commit(oid)?;
+        let tags = tagindex.get(&oid).map_or(&[][..], Vec::as_slice);
+        if let Some((rv, _)) = release_of(repo, tags, &commit, &options)? {
+            releases.push((commit.time().seconds(), commit.id(), rv));
+        }
+    }
+    releases.sort_by_key(|(time, _, _)| *time);
+    Ok(releases.into_iter().map(|(_, oid, rv)| (oid, rv)).collect())
+}
+
+/// The version named by the message of a release commit. Only the
+/// word right after the prefix counts, so numbers later in the
+/// message (`release: 1.2.3 for ticket 4567`) are never picked up.
+fn commit_release(commit: &Commit, options: &ResolveOptions) -> Option<SemanticVersion> {
+    let rest = options.strip_release_prefix(commit.message()?)?;
+    let text = rest.split_whitespace().next()?;
+    version_from_string(text, Some(commit), options)
+}
+
+/// The first of the tags of commit that names a version, with that version
+fn tag_release(
+    repo: &Repository,
+    tags: &[String],
+    commit: &Commit,
+    options: &ResolveOptions,
+) -> Option<(String, SemanticVersion)> {
+    for name in tags {
+        let tag = match resolve_tag(repo, name) {
+            Ok(tag) if tag.target == commit.id() => tag,
+            _ => continue,
+        };
+        if let Some(rv) = tag_version(&tag, Some(commit), options) {
+            return Some((tag.name, rv));
+        }
+    }
+    None
+}
+
+/// The highest version any tag reachable from the start commit
+/// names, with the name of the tag. None if no tag names a version.
+fn highest_reachable_tag(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<Option<(String, SemanticVersion)>, VersionError> {
+    let start = start_commit(repo, options)?;
+    let mut highest: Option<(String, SemanticVersion)> = None;
+    for (target, names) in tag_index(repo, options)? {
+        if target != start && !repo.graph_descendant_of(start, target)? {
+            continue;
+        }
+        for name in names {
+            let rv = match resolve_tag(repo, &name)
+                .ok()
+                .and_then(|tag| tag_version(&tag, None, options))
+            {
+                Some(rv) => rv,
+                None => continue,
+            };
+            if highest.as_ref().is_none_or(|(_, hv)| rv > *hv) {
+                highest = Some((name, rv));
+            }
+        }
+    }
+    Ok(highest)
+}
+
+/// The short id of object, as git abbreviates it but at least
+/// `options.short_len` characters long. Every commit id in a version
+/// comes from here so build metadata is the same however it was found.
+/// With `options.full_hash` the id is not abbreviated at all.
+pub fn abbreviate(object: &Object, options: &ResolveOptions) -> Result<String, VersionError> {
+    if options.full_hash {
+        return Ok(object.id().to_string());
+    }
+    let short = object.short_id()?.as_str().unwrap_or("0000000").to_string();
+    match options.short_len {
+        Some(len) if len > short.len() => {
+            let mut full = object.id().to_string();
+            full.truncate(len);
+            Ok(full)
+        }
+        _ => Ok(short),
+    }
+}
+
+/// The version of head_oid relative to the given tag, regardless of
+/// any release commits or other tags in between
+fn from_tag_version(
+    repo: &Repository,
+    options: &ResolveOptions,
+    name: &str,
+    head_oid: Oid,
+    head_short: String,
+) -> Result<PatchVersion, VersionError> {
+    let target = repo
+        .resolve_reference_from_short_name(name)
+        .and_then(|r| r.peel_to_commit())
+        .map_err(|_| VersionError::Generic(format!("tag {} does not exist", name)))?;
+    let target_oid = target.id();
+
+    if target_oid != head_oid && !repo.graph_descendant_of(head_oid, target_oid)? {
+        return Err(VersionError::Generic(format!(
+            "tag {} is not an ancestor of HEAD",
+            name
+        )));
+    }
+
+    // parsed like any other tag, tag_prefix and tag_message_version apply
+    let rv = resolve_tag(repo, name)
+        .ok()
+        .and_then(|tag| tag_version(&tag, Some(&target), options))
+        .ok_or_else(|| VersionError::Generic(format!("tag {} is not a version", name)))?;
+
+    // count like the normal walk does, a tag that is only reachable
+    // through a merge is counted on the full history
+    let mut count = 0;
+    let mut found = false;
+    for oid in history_walk(repo, options)? {
+        if oid? == target_oid {
+            found = true;
+            break;
+        }
+        count += 1;
+    }
+    if !found {
+        count = repo.graph_ahead_behind(head_oid, target_oid)?.0;
+    }
+
+    debug!("from-tag: {:?} {:?} {}", name, rv, count);
+
+    let mut version = PatchVersion::new(rv, count, None, Some(head_oid), Some(head_short));
+    version.baseline = Some(Baseline {
+        oid: target_oid,
+        source: format!("tag {}", name),
+    });
+    Ok(version)
+}
+
+/// The version a note in [`NOTES_REF`] pins the start commit to, if
+/// it has one, in place of what branch and history say. A note that
+/// is no version is ignored with a warning.
+fn note_version(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<Option<SemanticVersion>, VersionError> {
+    if options.rev.is_none() && head_is_unborn(repo) {
+        return Ok(None);
+    }
+    let oid = start_commit(repo, options)?;
+    let note = match repo.find_note(Some(NOTES_REF), oid) {
+        Ok(note) => note,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let text = note.message().unwrap_or("").trim();
+    let commit = repo.find_commit(oid)?;
+    match version_from_string(text, Some(&commit), options) {
+        Some(mut v) => {
+            info!("note: {} pins {}", NOTES_REF, v);
+            // the commit is build metadata like in any other version
+            v.build = v.commit.clone();
+            v.distance = Some(0);
+            Ok(Some(v))
+        }
+        None => {
+            warn!("ignoring note on {}, {:?} is no version", oid, text);
+            Ok(None)
+        }
+    }
+}
+
+/// The commit whose version is wanted, HEAD unless options.rev is set
+fn start_commit(repo: &Repository, options: &ResolveOptions) -> Result<Oid, VersionError> {
+    match options.rev {
+        Some(ref rev) => repo
+            .revparse_single(rev)
+            .and_then(|obj| obj.peel_to_commit())
+            .map(|commit| commit.id())
+            .map_err(|e| {
+                VersionError::Generic(format!(
+                    "cannot resolve '{}' to a commit: {}",
+                    rev,
+                    e.message()
+                ))
+            }),
+        None => {
+            let head = repo.head()?;
+            Ok(head.target().ok_or(VersionError::from("no target"))?)
+        }
+    }
+}
+
+/// The commits head_version looks at, newest first
+fn history_walk<'r>(
+    repo: &'r Repository,
+    options: &ResolveOptions,
+) -> Result<git2::Revwalk<'r>, VersionError> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(start_commit(repo, options)?)?;
+    // topological order keeps children before their parents even if
+    // commit times are equal or skewed
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    if options.count_mode == CountMode::FirstParent {
+        revwalk.simplify_first_parent()?;
+    }
+    Ok(revwalk)
+}
+
+/// Subject lines of the commits since the last release, newest first
+fn commits_since_release(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<Vec<String>, VersionError> {
+    let head = head_version(repo, options)?;
+    let mut subjects = Vec::new();
+    for oid in history_walk(repo, options)? {
+        if subjects.len() >= head.patch_count {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        if counts(&commit, options) {
+            subjects.push(commit.summary().unwrap_or("").to_string());
+        }
+    }
+    Ok(subjects)
+}
+
+/// Render a unix timestamp as an ISO-8601 date (UTC)
+fn iso_date(secs: i64) -> String {
+    let (year, month, day) = civil_date(secs);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Substitute `{date}` (ISO-8601) and `{year}` with the UTC date of
+/// the unix timestamp secs
+fn render_date(template: &str, secs: i64) -> String {
+    template
+        .replace("{date}", &iso_date(secs))
+        .replace("{year}", &civil_date(secs).0.to_string())
+}
+
+/// The unix timestamp of midnight (UTC) of an ISO-8601 date
+/// (YYYY-MM-DD), None if s is no valid date
+pub fn parse_iso_date(s: &str) -> Option<i64> {
+    let mut parts = s.splitn(3, '-');
+    let mut field = |len: usize| {
+        parts
+            .next()
+            .filter(|p| p.len() == len && p.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|p| p.parse::<i64>().ok())
+    };
+    let (year, month, day) = (field(4)?, field(2)?, field(2)?);
+    if !(1..=12).contains(&month) || day < 1 {
+        return None;
+    }
+    // days from civil, the inverse of civil_date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let secs = (era * 146097 + doe - 719468) * 86400;
+    // 2023-02-30 would roll over into March
+    (civil_date(secs) == (year, month, day)).then_some(secs)
+}
+
+/// Year, month and day of a unix timestamp (UTC)
+fn civil_date(secs: i64) -> (i64, i64, i64) {
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = secs.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// A tag as grelly sees it
+#[derive(Debug)]
+pub struct TagInfo {
+    pub name: String,
+    /// the commit the tag points to, None if it can't be resolved
+    pub target: Option<Oid>,
+    /// None if the name is not a version (or lacks the tag prefix)
+    pub version: Option<SemanticVersion>,
+}
+
+/// Every tag in the repository, sorted by name, with its target and
+/// the version it names.
+pub fn list_tags(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<Vec<TagInfo>, VersionError> {
+    let names = repo.tag_names(None)?;
+    let mut tags: Vec<TagInfo> = names
+        .iter()
+        .flatten()
+        .map(|name| {
+            let tag = resolve_tag(repo, name).ok();
+            let version = tag
+                .as_ref()
+                .filter(|_| options.accepts_tag(name))
+                .and_then(|tag| tag_version(tag, None, options));
+            TagInfo {
+                name: name.to_string(),
+                target: tag.map(|tag| tag.target),
+                version,
+            }
+        })
+        .collect();
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tags)
+}
+
+/// Map every commit that carries a tag to the tag names.
+///
+/// This reads the refs in one pass instead of looking up each tag by
+/// its short name. Packed refs already know the commit a tag points
+/// to, only loose tags need a look at the tag object. The names are
+/// not resolved or parsed here, that is left to the walk. Tags whose
+/// objects are missing are left out with a warning, or are an error
+/// with options.require_tags.
+fn tag_index(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<HashMap<Oid, Vec<String>>, VersionError> {
+    let mut index: HashMap<Oid, Vec<String>> = HashMap::new();
+    let mut dropped = Vec::new();
+    let odb = repo.odb()?;
+
+    for reference in repo.references_glob("refs/tags/*")? {
+        let reference = reference?;
+        let name = match reference.shorthand() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        if !options.accepts_tag(&name) {
+            continue;
+        }
+
+        let target = match reference.target_peel() {
+            Some(oid) => oid,
+            None => match reference.target() {
+                Some(oid) => match repo.find_tag(oid) {
+                    Ok(tag) => tag.target_id(),
+                    // a lightweight tag, oid is the commit
+                    Err(_) => oid,
+                },
+                None => continue,
+            },
+        };
+
+        // shallow clones may have the ref but not the objects
+        if !odb.exists(target) {
+            dropped.push(name);
+            continue;
+        }
+        index.entry(target).or_default().push(name);
+    }
+
+    if !dropped.is_empty() {
+        let message = format!(
+            "tags pointing to missing objects (a shallow clone?): {}",
+            dropped.join(", ")
+        );
+        if options.require_tags {
+            return Err(VersionError::Generic(message));
+        }
+        warn!("ignoring {}", message);
+    }
+
+    Ok(index)
+}
+
+/// True if name matches the glob pattern, `*` matches any text
+/// (also none), `?` exactly one character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    // where the last * was and the name position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ni));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == n[ni] => {
+                pi += 1;
+                ni += 1;
+            }
+            _ => match star {
+                // let the last * take one more character
+                Some((spi, sni)) => {
+                    star = Some((spi, sni + 1));
+                    pi = spi + 1;
+                    ni = sni + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[derive(Debug)]
+struct FullTag {
+    name: String,
+    target: Oid,
+    /// message of an annotated tag
+    message: Option<String>,
+}
+
+/// The version a tag names, by its name without the tag prefix or,
+/// with tag_message_version, by its message: the first word of the
+/// first line with at least major.minor, as in `Version 1.4.0`.
+fn tag_version(
+    tag: &FullTag,
+    commit: Option<&Commit>,
+    options: &ResolveOptions,
+) -> Option<SemanticVersion> {
+    let version_name = match options.tag_prefix {
+        Some(ref prefix) => tag.name.strip_prefix(prefix.as_str()).unwrap_or(&tag.name),
+        None => &tag.name,
+    };
+    if let Some(rv) = version_from_string(version_name, commit, options) {
+        return Some(rv);
+    }
+    if !options.tag_message_version {
+        return None;
+    }
+    tag.message
+        .as_deref()?
+        .lines()
+        .next()?
+        .split_whitespace()
+        .map(|word| word.trim_end_matches([',', ';', ':', ')', '!']))
+        .filter(|word| word.contains('.'))
+        .find_map(|word| version_from_string(word, commit, options))
+}
+
+fn resolve_tag(repo: &Repository, name: &str) -> Result<FullTag, git2::Error> {
+    let tref = repo.resolve_reference_from_short_name(name)?;
+    // lightweight tags point straight at the commit
+    let (target, message) = match tref.peel_to_tag() {
+        Ok(tag) => (tag.target_id(), tag.message().map(String::from)),
+        Err(_) => (tref.peel_to_commit()?.id(), None),
+    };
+
+    Ok(FullTag {
+        name: name.to_string(),
+        target,
+        message,
+    })
+}
+
+/// Build the signature used for release commits and tags.
+/// Explicit name/email win over user.name/user.email from the
+/// git config, a placeholder is used if neither is set.
+pub fn release_signature(
+    repo: &Repository,
+    name: Option<&str>,
+    email: Option<&str>,
+) -> Result<Signature<'static>, VersionError> {
+    let config = repo.config()?;
+
+    let name = match name {
+        Some(n) => n.to_string(),
+        None => config
+            .get_string("user.name")
+            .unwrap_or_else(|_| String::from("grelly")),
+    };
+    let email = match email {
+        Some(e) => e.to_string(),
+        None => config
+            .get_string("user.email")
+            .unwrap_or_else(|_| String::from("grelly@localhost")),
+    };
+
+    Ok(Signature::now(&name, &email)?)
+}
+
+/// Default template for the name of release tags
+pub const DEFAULT_TAG_FORMAT: &str = "v{major}.{minor}.{patch}";
+
+/// Default template for the message of release tags
+pub const DEFAULT_TAG_MESSAGE_FORMAT: &str = "Release {major}.{minor}.{patch}";
+
+/// Default name of the changes file of a release
+pub const DEFAULT_CHANGES_NAME: &str = "changes.{version}";
+
+/// Which version component a release increments
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bump {
+    /// x.0.0
+    Major,
+    /// 1.x.0
+    #[default]
+    Minor,
+    /// 1.2.x
+    Patch,
+}
+
+impl Bump {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bump::Major => "major",
+            Bump::Minor => "minor",
+            Bump::Patch => "patch",
+        }
+    }
+}
+
+/// The version a release from current would get. Bumping a
+/// component resets all lower ones, ident and metadata are dropped.
+pub fn next_version(current: &SemanticVersion, bump: Bump) -> SemanticVersion {
+    next_version_by(current, bump, 1)
+}
+
+/// Like [`next_version`], but the component is incremented by n,
+/// skipping the versions in between (1.2.x by 3 minor is 1.5.0).
+pub fn next_version_by(current: &SemanticVersion, bump: Bump, n: usize) -> SemanticVersion {
+    let (major, minor, patch) = match bump {
+        Bump::Major => (current.major + n, 0, 0),
+        Bump::Minor => (current.major, current.minor + n, 0),
+        Bump::Patch => (current.major, current.minor, current.patch + n),
+    };
+    SemanticVersion::new(major, minor, patch, None, None, None)
+}
+
+/// The component that changed from one version to a higher one, None
+/// if to is not higher than from. Idents and metadata are ignored.
+pub fn bump_between(from: &SemanticVersion, to: &SemanticVersion) -> Option<Bump> {
+    if to <= from {
+        None
+    } else if to.major != from.major {
+        Some(Bump::Major)
+    } else if to.minor != from.minor {
+        Some(Bump::Minor)
+    } else if to.patch != from.patch {
+        Some(Bump::Patch)
+    } else {
+        // only the ident differs, e.g. 1.2.3-rc.1 to 1.2.3
+        None
+    }
+}
+
+/// Settings for making a release
+#[derive(Debug, Clone)]
+pub struct ReleaseOptions {
+    /// component to increment, None is patch on a release branch
+    /// (its major.minor is fixed) and minor everywhere else
+    pub bump: Option<Bump>,
+    /// how much the bumped component grows, at least 1
+    pub bump_by: usize,
+    /// only print what would be created, touch nothing
+    pub dry_run: bool,
+    /// template for the tag name, see [`render_template`]
+    pub tag_format: String,
+    /// template for the tag message, see [`render_template`], may
+    /// also use `{date}` and `{year}` of the release commit
+    pub tag_message_format: String,
+    /// file with the template for the changes file, may use
+    /// `{version}`, `{date}`, `{year}` and `{commits}` (one
+    /// `- subject` line per commit since the last release)
+    pub changes_template: Option<PathBuf>,
+    /// directory of the changes file, relative to the working directory
+    pub changes_dir: PathBuf,
+    /// name of the changes file, may use `{version}` and the
+    /// placeholders of [`render_template`]
+    pub changes_name: String,
+    /// GPG-sign the tag with user.signingkey
+    pub sign: bool,
+    /// replace an existing tag of the same name
+    pub force: bool,
+    /// only tag HEAD, without a changes file and release commit
+    pub tag_only: bool,
+    /// commit and tag as the author of HEAD instead of the signature
+    pub inherit_author: bool,
+    /// release even if a tag has a version as high as the new one
+    pub allow_downgrade: bool,
+    /// shell command run after tagging, with `GRELLY_VERSION` and
+    /// `GRELLY_TAG` in its environment
+    pub post_release_hook: Option<String>,
+    /// fail the release if the hook fails, else only warn
+    pub hook_required: bool,
+    /// release from feature, fix, other branches and a detached
+    /// HEAD, not only from main and release branches
+    pub allow_any_branch: bool,
+}
+
+impl Default for ReleaseOptions {
+    fn default() -> Self {
+        Self {
+            bump: None,
+            bump_by: 1,
+            dry_run: false,
+            tag_format: String::from(DEFAULT_TAG_FORMAT),
+            tag_message_format: String::from(DEFAULT_TAG_MESSAGE_FORMAT),
+            changes_template: None,
+            changes_dir: PathBuf::new(),
+            changes_name: String::from(DEFAULT_CHANGES_NAME),
+            sign: false,
+            force: false,
+            tag_only: false,
+            inherit_author: false,
+            allow_downgrade: false,
+            post_release_hook: None,
+            hook_required: false,
+            allow_any_branch: false,
+        }
+    }
+}
+
+/// Substitute the `{major}`, `{minor}`, `{patch}` and `{ident}`
+/// placeholders in template with the fields of version.
+/// A missing ident is rendered as the empty string.
+pub fn render_template(template: &str, version: &SemanticVersion) -> String {
+    template
+        .replace("{major}", &version.major.to_string())
+        .replace("{minor}", &version.minor.to_string())
+        .replace("{patch}", &version.patch.to_string())
+        .replace("{ident}", version.ident.as_deref().unwrap_or(""))
+}
+
+/// Render an output template with the placeholders `{major}`,
+/// `{minor}`, `{patch}`, `{ident}`, `{commit}` and `{branch}`, empty
+/// if the version has no such part. `{{` and `}}` are literal braces,
+/// an unknown placeholder or a lone brace is an error.
+pub fn render_output(
+    template: &str,
+    version: &SemanticVersion,
+    branch: &str,
+) -> Result<String, VersionError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let end = rest
+            .find('}')
+            .filter(|_| rest.starts_with('{'))
+            .ok_or_else(|| VersionError::Generic(format!("unmatched brace in '{}'", template)))?;
+        let value = match &rest[1..end] {
+            "major" => version.major.to_string(),
+            "minor" => version.minor.to_string(),
+            "patch" => version.patch.to_string(),
+            "ident" => version.ident.clone().unwrap_or_default(),
+            "commit" => version.commit.clone().unwrap_or_default(),
+            "branch" => branch.to_string(),
+            other => {
+                return Err(VersionError::Generic(format!(
+                    "unknown placeholder {{{}}} in '{}'",
+                    other, template
+                )))
+            }
+        };
+        out.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// A tag template without any numeric placeholder would give
+/// every release the same tag name.
+fn validate_tag_format(template: &str) -> Result<(), VersionError> {
+    if ["{major}", "{minor}", "{patch}"]
+        .iter()
+        .any(|p| template.contains(p))
+    {
+        Ok(())
+    } else {
+        Err(VersionError::Generic(format!(
+            "tag format '{}' needs at least one of {{major}}, {{minor}} or {{patch}}",
+            template
+        )))
+    }
+}
+
+/// What [`main_release`] made, or with dry_run would make
+#[derive(Debug)]
+pub struct Release {
+    pub version: SemanticVersion,
+    /// the changes file, None with tag_only
+    pub changes_file: Option<PathBuf>,
+    /// message of the release commit, None with tag_only
+    pub message: Option<String>,
+    pub tag: String,
+}
+
+/// Make a release
+pub fn main_release(
+    repo: &Repository,
+    signature: &Signature,
+    resolve: &ResolveOptions,
+    options: &ReleaseOptions,
+) -> Result<Release, VersionError> {
+    validate_tag_format(&options.tag_format)?;
+
+    // the author of HEAD, at the time of the given signature
+    let inherited;
+    let signature = if options.inherit_author {
+        let head = repo.head()?.peel_to_commit()?;
+        let author = head.author();
+        inherited = Signature::new(
+            author.name().unwrap_or(""),
+            author.email().unwrap_or(""),
+            &signature.when(),
+        )?;
+        &inherited
+    } else {
+        signature
+    };
+
+    if repo.is_bare() && !options.dry_run && !options.tag_only {
+        return Err(VersionError::from(
+            "cannot release from a bare repository, the changes file needs a working directory",
+        ));
+    }
+
+    // releases are cut from main and release branches only
+    let branch = branch_version(repo, resolve)?;
+    if !options.allow_any_branch
+        && !matches!(branch, BranchVersion::Master | BranchVersion::Release(_))
+    {
+        let head = repo.head()?;
+        return Err(VersionError::Generic(format!(
+            "cannot release from {} branch {}, use --allow-any-branch to release anyway",
+            branch.kind(),
+            head.shorthand()
+                .filter(|_| head.is_branch())
+                .unwrap_or("HEAD")
+        )));
+    }
+
+    // look for the key before anything is written
+    let signing_key =
+        if options.sign {
+            Some(repo.config()?.get_string("user.signingkey").map_err(|_| {
+                VersionError::from("cannot sign the tag, user.signingkey is not set")
+            })?)
+        } else {
+            None
+        };
+
+    if options.bump_by == 0 {
+        return Err(VersionError::from(
+            "cannot bump by 0, the release would repeat a version",
+        ));
+    }
+
+    // a release branch only gets patch releases of its major.minor
+    let release_branch = match branch {
+        BranchVersion::Release(ref v) => Some(v),
+        _ => None,
+    };
+    let bump = match (options.bump, release_branch) {
+        (Some(bump), Some(v)) if bump != Bump::Patch => {
+            return Err(VersionError::Generic(format!(
+                "refusing {} release on release branch {}.{}, only patch releases are allowed",
+                bump.name(),
+                v.major,
+                v.minor
+            )));
+        }
+        (Some(bump), _) => bump,
+        (None, Some(_)) => Bump::Patch,
+        (None, None) => Bump::default(),
+    };
+
+    let current_version = main_version(repo, resolve)?;
+    if current_version.patch == 0 {
+        return Err(VersionError::Generic(format!(
+            "refusing {} release: patch version is zero, we are already on release commit {}",
+            bump.name(),
+            current_version
+        )));
+    }
+
+    let next_version = next_version_by(&current_version, bump, options.bump_by);
+
+    if !options.allow_downgrade {
+        // newer lines released from elsewhere don't block a patch
+        // release on a release branch
+        let highest = list_tags(repo, resolve)?
+            .into_iter()
+            .filter_map(|tag| tag.version.map(|v| (tag.name, v)))
+            .filter(|(_, v)| {
+                release_branch.is_none_or(|b| (v.major, v.minor) == (b.major, b.minor))
+            })
+            .max_by(|a, b| a.1.cmp(&b.1));
+        if let Some((name, highest)) = highest {
+            if next_version <= highest {
+                return Err(VersionError::Generic(format!(
+                    "refusing to release {}, tag {} already has version {}",
+                    next_version, name, highest
+                )));
+            }
+        }
+    }
+
+    let filename = options.changes_dir.join(
+        render_template(&options.changes_name, &next_version)
+            .replace("{version}", &next_version.to_string()),
+    );
+
+    let prefix = resolve
+        .release_prefixes
+        .first()
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_RELEASE_PREFIX);
+    let message = format!("{} {}", prefix, next_version);
+
+    // dates are those of the release commit, i.e. the signature
+    let when = signature.when().seconds();
+
+    let tag_name = render_template(&options.tag_format, &next_version);
+    if !options.force
+        && repo
+            .find_reference(&format!("refs/tags/{}", tag_name))
+            .is_ok()
+    {
+        return Err(VersionError::Generic(format!(
+            "tag {} already exists, use --force to replace it",
+            tag_name
+        )));
+    }
+    let tag_message = render_date(
+        &render_template(&options.tag_message_format, &next_version),
+        when,
+    );
+
+    if options.dry_run {
+        return Ok(Release {
+            version: next_version,
+            changes_file: Some(filename).filter(|_| !options.tag_only),
+            message: Some(message).filter(|_| !options.tag_only),
+            tag: tag_name,
+        });
+    }
+
+    let target = if options.tag_only {
+        repo.head()?.peel_to_commit()?.id()
+    } else {
+        let changes_text = match options.changes_template {
+            Some(ref path) => {
+                let template = fs::read_to_string(path)?;
+                let commits: Vec<String> = commits_since_release(repo, resolve)?
+                    .iter()
+                    .map(|s| format!("- {}", s))
+                    .collect();
+                render_date(
+                    &template
+                        .replace("{version}", &next_version.to_string())
+                        .replace("{commits}", &commits.join("\n")),
+                    when,
+                )
+            }
+            None => format!("Changes for version {}\n", next_version),
+        };
+        commit_changes(repo, signature, &filename, &changes_text, &message)?
+    };
+
+    match signing_key {
+        Some(ref key) => signed_tag(
+            repo,
+            key,
+            &tag_name,
+            target,
+            signature,
+            &tag_message,
+            options.force,
+        )?,
+        None => {
+            let targetobj = repo.find_object(target, None)?;
+            repo.tag(
+                &tag_name,
+                &targetobj,
+                signature,
+                &tag_message,
+                options.force,
+            )?;
+        }
+    }
+
+    if let Some(ref hook) = options.post_release_hook {
+        run_hook(repo, hook, &next_version, &tag_name, options.hook_required)?;
+    }
+
+    Ok(Release {
+        version: next_version,
+        changes_file: Some(filename).filter(|_| !options.tag_only),
+        message: Some(message).filter(|_| !options.tag_only),
+        tag: tag_name,
+    })
+}
+
+/// Write the changes file, add it to the index and commit it on
+/// HEAD, returns the new commit
+fn commit_changes(
+    repo: &Repository,
+    signature: &Signature,
+    filename: &Path,
+    text: &str,
+    message: &str,
+) -> Result<Oid, VersionError> {
+    let workdir = repo.workdir().ok_or(git2::Error::from_str("no workdir"))?;
+    let changes = workdir.join(filename);
+    // the index wants the path relative to the working directory
+    let relative = changes.strip_prefix(workdir).map_err(|_| {
+        VersionError::Generic(format!(
+            "changes file {} is outside the working directory",
+            changes.display()
+        ))
+    })?;
+    if let Some(dir) = changes.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut cfile = File::create(&changes)?;
+    cfile.write_all(text.as_bytes())?;
+    cfile.flush()?;
+
+    let obj = repo.head()?.resolve()?.peel(ObjectType::Commit)?;
+
+    let mut index = repo.index()?;
+    index.add_path(relative)?;
+
+    let oid = index.write_tree()?;
+    let parent_commit = obj
+        .into_commit()
+        .map_err(|_| git2::Error::from_str("not a commit"))?;
+    let tree = repo.find_tree(oid)?;
+
+    Ok(repo.commit(
+        Some("HEAD"), //  point HEAD to our new commit
+        signature,    // author
+        signature,    // committer
+        message,      // commit message
+        &tree,        // tree
+        &[&parent_commit],
+    )?)
+}
+
+/// Run the post release hook with `sh -c` in the working directory,
+/// a failing hook is an error if required, else only a warning
+fn run_hook(
+    repo: &Repository,
+    hook: &str,
+    version: &SemanticVersion,
+    tag_name: &str,
+    required: bool,
+) -> Result<(), VersionError> {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(hook)
+        .env("GRELLY_VERSION", version.to_string())
+        .env("GRELLY_TAG", tag_name);
+    if let Some(workdir) = repo.workdir() {
+        cmd.current_dir(workdir);
+    }
+    let status = cmd.status()?;
+
+    if status.success() {
+        Ok(())
+    } else if required {
+        Err(VersionError::Generic(format!(
+            "post release hook failed ({})",
+            status
+        )))
+    } else {
+        warn!("post release hook failed ({})", status);
+        Ok(())
+    }
+}
+
+/// git2 cannot sign tags, so leave that to `git tag -s`
+fn signed_tag(
+    repo: &Repository,
+    key: &str,
+    name: &str,
+    target: Oid,
+    signature: &Signature,
+    message: &str,
+    force: bool,
+) -> Result<(), VersionError> {
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir").arg(repo.path()).arg("tag");
+    if force {
+        cmd.arg("--force");
+    }
+    let output = cmd
+        .args(["--sign", "--local-user", key, "--message", message])
+        .arg(name)
+        .arg(target.to_string())
+        .env("GIT_COMMITTER_NAME", signature.name().unwrap_or(""))
+        .env("GIT_COMMITTER_EMAIL", signature.email().unwrap_or(""))
+        .output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(VersionError::Generic(format!(
+            "signing tag {} failed: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Lowercase name with every run of characters other than ASCII
+/// letters and digits replaced by a single `-`, as in team-foo-bar
+/// for Team/foo_bar
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Merge one component of the branch and head versions, a zero
+/// on either side gives way to the other one.
+fn nmerge(
+    component: &'static str,
+    branchv: &SemanticVersion,
+    headv: &SemanticVersion,
+    get: fn(&SemanticVersion) -> usize,
+) -> Result<usize, VersionError> {
+    let (branch, head) = (get(branchv), get(headv));
+    if branch == 0 || head == 0 {
+        Ok(head + branch)
+    } else if branch == head {
+        Ok(branch)
+    } else {
+        Err(VersionError::Mismatch {
+            component,
+            branch,
+            head,
+            branch_version: branchv.to_string(),
+            head_version: headv.to_string(),
+        })
+    }
+}
+
+/// Merge the idents of the branch and head versions, a missing one
+/// gives way to the other, if they differ the branch wins.
+fn smerge(branch: Option<String>, head: Option<String>) -> Option<String> {
+    match (branch, head) {
+        (Some(branch), Some(head)) if branch != head => {
+            warn!(
+                "branch ident {} differs from release ident {}, using {}",
+                branch, head, branch
+            );
+            Some(branch)
+        }
+        (branch, head) => branch.or(head),
+    }
+}
+
+/// The version of the history merged with what the branch says,
+/// before pre-release, dirty suffix and build metadata options
+fn branch_history_version(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<SemanticVersion, VersionError> {
+    // check the branch itself for version information
+    let branch = branch_version(repo, options)?;
+    info!("branch: {:?}", branch);
+
+    let head = head_version(repo, options)?;
+    let headv = head.semver();
+
+    info!("head: {:?}", head);
+
+    let mut bv = match branch {
+        BranchVersion::Master | BranchVersion::Detached => head.semver(),
+        BranchVersion::Release(branchv) => {
+            let major = nmerge("major", &branchv, &headv, |v| v.major)?;
+            let minor = nmerge("minor", &branchv, &headv, |v| v.minor)?;
+            let patch = headv.patch;
+
+            let ident = smerge(branchv.ident, headv.ident);
+
+            SemanticVersion::new(major, minor, patch, ident, headv.commit, headv.build)
+        }
+        BranchVersion::Feature(f) | BranchVersion::Fix(f) => {
+            // nested branches keep all segments, feature/team/foo is team-foo
+            let f = if options.slug_branch {
+                slugify(&f)
+            } else {
+                sanitize_ident(&f)
+            };
+            // every build of the branch gets its own ident, foo.4
+            let ident = if options.pre_counter {
+                format!("{}.{}", f, head.patch_count)
+            } else {
+                f
+            };
+            SemanticVersion::new(
+                headv.major,
+                headv.minor,
+                headv.patch,
+                Some(ident),
+                headv.commit,
+                headv.build,
+            )
+        }
+        BranchVersion::Other(_f) => SemanticVersion::new(
+            headv.major,
+            headv.minor,
+            headv.patch,
+            Some(String::from("other")),
+            headv.commit,
+            headv.build,
+        ),
+    };
+    bv.distance = Some(head.patch_count);
+
+    Ok(bv)
+}
+
+/// Return a version for the current git commit.
+pub fn main_version(
+    repo: &Repository,
+    options: &ResolveOptions,
+) -> Result<SemanticVersion, VersionError> {
+    if let Some(ref pre) = options.pre {
+        validate_prerelease(pre)?;
+    }
+
+    // a note pins the version, whatever the branch and history say
+    let mut bv = match note_version(repo, options)? {
+        Some(v) => v,
+        None => branch_history_version(repo, options)?,
+    };
+
+    if let Some(ref pre) = options.pre {
+        bv.ident = Some(pre.clone());
+    }
+    if !options.dirty_suffix.is_empty() && options.rev.is_none() && is_dirty(repo)? {
+        bv.ident = Some(match bv.ident {
+            Some(ident) => format!("{}{}", ident, options.dirty_suffix),
+            None => options
+                .dirty_suffix
+                .trim_start_matches(['-', '.'])
+                .to_string(),
+        });
+    }
+    // the dirty suffix is appended as given
+    bv.ident = clean_ident(bv.ident);
+    if !options.build_metadata {
+        bv.build = None;
+    }
+
+    if options.strict {
+        if let Some((name, tv)) = highest_reachable_tag(repo, options)? {
+            if (bv.major, bv.minor) < (tv.major, tv.minor) {
+                return Err(VersionError::Generic(format!(
+                    "version {} is below the reachable tag {}, branch and tags disagree",
+                    bv, name
+                )));
+            }
+        }
+    }
+
+    Ok(bv)
+}
+
+/// True if tracked files in the working tree or the index differ
+/// from HEAD. Untracked files don't count, a bare repository is
+/// never dirty.
+pub fn is_dirty(repo: &Repository) -> Result<bool, VersionError> {
+    if repo.is_bare() {
+        return Ok(false);
+    }
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    Ok(!repo.statuses(Some(&mut opts))?.is_empty())
+}
+
+/// Make ident a valid SemVer pre-release: every run of characters
+/// other than `[0-9A-Za-z-]` becomes a `-`, empty identifiers are
+/// dropped and leading zeros are stripped from numeric ones, so
+/// `team/foo_bar..007` is `team-foo-bar.7`. The result is empty if
+/// nothing of ident is left.
+pub fn sanitize_ident(ident: &str) -> String {
+    sanitize_identifiers(ident, true)
+}
+
+/// Make build a valid SemVer build metadata like [`sanitize_ident`]
+/// does for idents, but numeric identifiers keep their leading
+/// zeros as the grammar allows them there.
+fn sanitize_build(build: &str) -> String {
+    sanitize_identifiers(build, false)
+}
+
+/// Dot-separated identifiers of `[0-9A-Za-z-]`, see [`sanitize_ident`]
+fn sanitize_identifiers(text: &str, strip_zeros: bool) -> String {
+    text.split('.')
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            let mut clean = String::with_capacity(id.len());
+            for c in id.chars() {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    clean.push(c);
+                } else if !clean.ends_with('-') {
+                    clean.push('-');
+                }
+            }
+            if strip_zeros && clean.len() > 1 && clean.chars().all(|c| c.is_ascii_digit()) {
+                let trimmed = clean.trim_start_matches('0');
+                if trimmed.is_empty() { "0" } else { trimmed }.to_string()
+            } else {
+                clean
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// ident sanitized, None if nothing of it is left. Every ident is
+/// cleaned where the version is built so all output formats agree.
+fn clean_ident(ident: Option<String>) -> Option<String> {
+    ident.map(|i| sanitize_ident(&i)).filter(|i| !i.is_empty())
+}
+
+/// Check pre against the SemVer pre-release grammar: dot-separated,
+/// non-empty identifiers of `[0-9A-Za-z-]`, numeric ones without
+/// leading zeros.
+fn validate_prerelease(pre: &str) -> Result<(), VersionError> {
+    let valid = pre.split('.').all(|id| {
+        !id.is_empty()
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !(id.len() > 1 && id.starts_with('0') && id.chars().all(|c| c.is_ascii_digit()))
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(VersionError::Generic(format!(
+            "invalid pre-release identifier '{}'",
+            pre
+        )))
+    }
+}
+
+/// Resolve the version of the commit HEAD points to.
+///
+/// This is the entry point for using grelly as a library, e.g. from
+/// a build script.
+pub fn resolve_version(repo: &Repository) -> Result<SemanticVersion, VersionError> {
+    main_version(repo, &ResolveOptions::default())
+}
+
+/// How [`write_version_file`] wraps the version
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileFormat {
+    /// the version and a newline
+    #[default]
+    Plain,
+    /// pub const VERSION: &str = "1.2.3";
+    Rust,
+}
+
+/// Write version to path, replacing the file if it exists.
+pub fn write_version_file(
+    path: &Path,
+    version: &str,
+    format: FileFormat,
+) -> Result<(), VersionError> {
+    let mut file = File::create(path)?;
+    match format {
+        FileFormat::Plain => writeln!(file, "{}", version)?,
+        FileFormat::Rust => writeln!(file, "pub const VERSION: &str = {:?};", version)?,
+    }
+    Ok(())
+}
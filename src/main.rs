@@ -1,472 +1,771 @@
-use clap::Parser;
-use git2::{Commit, ObjectType, Oid, Repository, Signature};
-use regex::{Match, Regex};
-use std::{collections::HashMap, fs::File, io::Write, path::PathBuf};
-use thiserror::Error;
-
-const DEBUG: bool = true;
-
-#[derive(Error, Debug)]
-pub enum VersionError {
-    #[error("Error: {0}")]
-    Generic(String),
-    #[error("git error")]
-    Git(#[from] git2::Error),
-    #[error("io error")]
-    Io(#[from] std::io::Error),
+use clap::{ArgAction, Parser, ValueEnum};
+use git2::{Repository, RepositoryInitOptions, Signature};
+use grelly::{
+    abbreviate, branch_version, bump_between, config::Config, default_branch_prefixes,
+    default_main_branches, docker_tags, head_version, latest_release, list_tags, main_release,
+    main_version, open_repository, open_submodule, parse_iso_date, release_signature, render,
+    render_json, render_output, write_version_file, BranchCategory, BranchVersion, Bump, CountMode,
+    FileFormat, Prefer, ReleaseOptions, ResolveOptions, SemanticVersion, Separators, VersionError,
+    VersionFormat, DEFAULT_CHANGES_NAME, DEFAULT_MAX_DEPTH, DEFAULT_RELEASE_PREFIX,
+    DEFAULT_SEPARATORS, DEFAULT_TAG_FORMAT, DEFAULT_TAG_MESSAGE_FORMAT,
+};
+use log::{Level, LevelFilter};
+use std::{
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+/// Exit code of --check-release if HEAD is not a release
+const EXIT_NOT_RELEASE: i32 = 2;
+
+/// How the resolved version is printed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// 1.2.3-ident
+    Plain,
+    /// {"schema":1,"version":"1.2.3","major":1,...}
+    Json,
+    /// GRELLY_VERSION=1.2.3 lines for sourcing or $GITHUB_ENV
+    Env,
+    /// 1.2.7 (1.2.5 + 2 commits), the release and the distance to it
+    Detailed,
+    /// 1.2.3rc1 or 1.2.3.dev4 for Python packages (PEP 440)
+    Pep440,
+    /// Docker image tags, one per line: 1.2.3, 1.2, 1 and latest on master;
+    /// only 1.2.3-ident for pre-releases
+    Docker,
 }
 
-impl From<&str> for VersionError {
-    fn from(s: &str) -> Self {
-        VersionError::Generic(s.to_string())
-    }
-}
-
-/// What the branch-name tells us about the version
-#[derive(Debug)]
-enum BranchVersion {
-    // master, main, release
-    Master,
-    // release: 1.2.3
-    Release(SemanticVersion),
-    // feature/myfeature
-    Feature(String),
-    // fix/myfix
-    Fix(String),
-    // other
-    Other(String),
+/// Which part of the version is printed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Show {
+    /// 1.2.3-ident+build
+    Full,
+    /// 1
+    Major,
+    /// 2
+    Minor,
+    /// 3
+    Patch,
+    /// 1.2
+    #[value(name = "major.minor")]
+    MajorMinor,
 }
 
-/// takes a repository and returns the branch name
-/// if the repository is a git repository, otherwise returns Error
-fn branch_version(repo: &Repository) -> Result<BranchVersion, VersionError> {
-    let head = repo.head()?;
-    let branch = head.shorthand().unwrap().to_lowercase();
-
-    match version_from_string(&branch, None) {
-        Some(v) => Ok(BranchVersion::Release(v)),
-        None => {
-            if branch == "master" || branch == "main" || branch == "release" {
-                Ok(BranchVersion::Master)
-            } else if branch.starts_with("feature/") {
-                Ok(BranchVersion::Feature(branch.clone().split_off(8)))
-            } else if branch.starts_with("fix/") {
-                Ok(BranchVersion::Fix(branch.clone().split_off(4)))
-            } else {
-                Ok(BranchVersion::Other(branch.to_string()))
-            }
+impl Show {
+    fn render(&self, v: &SemanticVersion) -> String {
+        match self {
+            Show::Full => v.to_string(),
+            Show::Major => v.major.to_string(),
+            Show::Minor => v.minor.to_string(),
+            Show::Patch => v.patch.to_string(),
+            Show::MajorMinor => format!("{}.{}", v.major, v.minor),
         }
     }
 }
 
-/// a major.minor.patch version
-#[derive(Debug)]
-struct SemanticVersion {
-    major: usize,
-    minor: usize,
-    patch: usize,
-    ident: Option<String>,
-    commit: Option<String>,
-}
+/// Find version for current git commit.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the Git Repository
+    #[arg(short, long, env = "GRELLY_GIT", default_value_t = String::from("."))]
+    git: String,
 
-impl SemanticVersion {
-    fn new(
-        major: usize,
-        minor: usize,
-        patch: usize,
-        ident: Option<String>,
-        commit: Option<String>,
-    ) -> Self {
-        Self {
-            major,
-            minor,
-            patch,
-            ident,
-            commit,
-        }
-    }
+    /// Open the repository as bare, without a working directory
+    #[arg(long, conflicts_with = "work_tree")]
+    bare: bool,
 
-    // fn from_triple(major: usize, minor: usize, patch: usize) -> Self {
-    //     SemanticVersion::new(major, minor, patch, None, None)
-    // }
+    /// Working directory, if it is not the parent of the git dir
+    #[arg(long)]
+    work_tree: Option<PathBuf>,
 
-    fn version_string(&self) -> String {
-        match self.ident {
-            Some(ref v) => format!("{}.{}.{}-{}", self.major, self.minor, self.patch, v),
-            None => format!("{}.{}.{}", self.major, self.minor, self.patch),
-        }
-    }
+    /// Compute the version of this submodule (name or path) of the repository
+    #[arg(long)]
+    submodule: Option<String>,
 
-    fn panoo_string(&self) -> String {
+    #[arg(short, long)]
+    release: bool,
 
-        let mut version = format!("P{}-{}", self.major, self.minor);
-        if self.patch > 0 {
-            version.push_str(&format!("-{}", self.patch));
-        }
-        if let Some(ref v) = self.ident {
-            version.push_str(&format!("-{}", v));
-        }
-        version
-        
-    }
+    /// Output panoo-style versions (P12-9)
+    #[arg(short, long)]
+    panoo: bool,
 
+    /// Author name for release commits and tags
+    /// [overrides author.name in .grelly.toml, then git config user.name]
+    #[arg(long)]
+    author_name: Option<String>,
+
+    /// Author email for release commits and tags
+    /// [overrides author.email in .grelly.toml, then git config user.email]
+    #[arg(long)]
+    author_email: Option<String>,
+
+    /// Release as the author of the HEAD commit
+    /// [overrides --author-name, --author-email and the config]
+    #[arg(long)]
+    inherit_author: bool,
+
+    /// Commit message prefix that marks a release commit (repeatable)
+    /// [overrides release_prefixes in .grelly.toml, default: release:]
+    #[arg(long = "release-prefix")]
+    release_prefixes: Vec<String>,
+
+    /// Branch name prefix and its category, e.g. feat/=feature or hotfix/=fix (repeatable)
+    /// [overrides branch_prefixes in .grelly.toml, default: feature/=feature fix/=fix]
+    #[arg(long = "branch-prefix", value_parser = parse_branch_prefix)]
+    branch_prefixes: Vec<(String, BranchCategory)>,
+
+    /// Branch that gets the plain version of its head, e.g. trunk (repeatable)
+    /// [overrides main_branches in .grelly.toml, default: master main release]
+    #[arg(long = "main-branch")]
+    main_branches: Vec<String>,
+
+    /// Accept tags and branches with leading zeros like v01.02.03 (read as 1.2.3)
+    #[arg(long)]
+    allow_leading_zeros: bool,
+
+    /// Compute the version of this revision instead of HEAD
+    #[arg(long, conflicts_with = "release")]
+    rev: Option<String>,
+
+    /// Count only the first-parent line or every commit since the release
+    #[arg(long, value_enum, default_value_t = CountMode::FirstParent)]
+    count_mode: CountMode,
+
+    /// Do not count merge commits in the patch distance
+    #[arg(long)]
+    skip_merges: bool,
+
+    /// Search at most this many commits for a release (0 = unlimited).
+    /// Without a release in range the patch is the number of commits searched
+    #[arg(long, default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+
+    /// Ignore commits older than this date (YYYY-MM-DD, UTC), as if the
+    /// history started there
+    #[arg(long, value_parser = parse_since)]
+    since: Option<i64>,
+
+    /// Without a release on the first-parent line, search merged branches too.
+    /// Walks the whole history, which can be slow in large repositories
+    #[arg(long)]
+    deep_search: bool,
+
+    /// Resolve at most this many tags, nearest to HEAD first (0 = unlimited).
+    /// Releases marked by tags past the limit are missed
+    #[arg(long, default_value_t = 0)]
+    max_tag_scan: usize,
+
+    /// Only tags starting with this prefix (e.g. v or release-) are releases
+    #[arg(long)]
+    tag_prefix: Option<String>,
+
+    /// Tags matching this glob (* and ?) are never releases, e.g. 'ci-*' (repeatable)
+    #[arg(long = "ignore-tag")]
+    ignore_tags: Vec<String>,
+
+    /// Fail if a tag points to a missing object (as in a shallow clone)
+    /// instead of ignoring the tag with a warning
+    #[arg(long)]
+    require_tags: bool,
+
+    /// Read the version from the message of annotated tags whose name is no version
+    #[arg(long)]
+    tag_message_version: bool,
+
+    /// Count from this tag, ignoring release commits and other tags
+    #[arg(long)]
+    from_tag: Option<String>,
+
+    /// Characters accepted between the numbers of a version, e.g. _ for 1_2_3
+    #[arg(long = "separator", value_parser = parse_separators, default_value = DEFAULT_SEPARATORS)]
+    separators: Separators,
+
+    /// Baseline version if the history has no release, e.g. 0.1.0
+    #[arg(long)]
+    initial_version: Option<String>,
+
+    /// Accept a short or v-prefixed --initial-version such as v1.2 (1.2.0)
+    #[arg(long, requires = "initial_version")]
+    lenient_versions: bool,
+
+    /// Which version wins if a release commit is also tagged and the two disagree
+    #[arg(long, value_enum, default_value_t = Prefer::Commit)]
+    prefer: Prefer,
+
+    /// Abbreviate the commit id to at least this many characters
+    #[arg(long, value_parser = clap::value_parser!(u8).range(4..=40))]
+    short_len: Option<u8>,
+
+    /// Use the full commit id instead of the abbreviated one
+    #[arg(long, conflicts_with = "short_len")]
+    full_hash: bool,
+
+    /// Leave the commit out of the version string (1.2.3 instead of 1.2.3+a1b2c3d)
+    #[arg(long)]
+    no_commit_metadata: bool,
+
+    /// Append this to the ident if tracked files have uncommitted changes, e.g. -dirty
+    #[arg(long, default_value = "")]
+    dirty_suffix: String,
+
+    /// Use a lowercase slug of feature and fix branch names as the ident,
+    /// e.g. team-foo for feature/team/foo
+    #[arg(long)]
+    slug_branch: bool,
+
+    /// Append the commit distance to the ident of feature and fix branches (1.2.3-foo.4),
+    /// --pre-counter=false keeps the plain branch name
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pre_counter: bool,
+
+    /// Fail if the version is below the highest reachable version tag
+    /// (by major.minor), which means branch and tags disagree
+    #[arg(long)]
+    strict: bool,
+
+    /// Pre-release identifier for the version, e.g. rc.1 or nightly
+    #[arg(long)]
+    pre: Option<String>,
+
+    /// Version component incremented by --release
+    /// [default: patch on a release branch, else minor]
+    #[arg(long, value_enum)]
+    bump: Option<Bump>,
+
+    /// Increment the component chosen by --bump by this much, e.g. --bump minor
+    /// --bump-by 3 releases 1.5.0 after 1.2.x
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), requires = "release")]
+    bump_by: u32,
+
+    /// With --release, print what would be committed and tagged without doing it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Template file for the changes file of a release, may use {version}, {date}, {year} and {commits}
+    #[arg(long)]
+    changes_template: Option<PathBuf>,
+
+    /// With --release, replace an existing tag of the same name
+    #[arg(long)]
+    force: bool,
+
+    /// With --release, tag HEAD as the next version without a changes file and release commit
+    #[arg(long)]
+    tag_only: bool,
+
+    /// With --release, allow a version that is not higher than every version tag
+    #[arg(long)]
+    allow_downgrade: bool,
+
+    /// Directory of the changes file of a release, relative to the working directory
+    #[arg(long)]
+    changes_path: Option<PathBuf>,
+
+    /// Name of the changes file, may use {version}, {major}, {minor}, {patch} and {ident}
+    #[arg(long, default_value = DEFAULT_CHANGES_NAME)]
+    changes_name: String,
+
+    /// Shell command to run after the release tag is created,
+    /// GRELLY_VERSION and GRELLY_TAG are set in its environment
+    #[arg(long)]
+    post_release_hook: Option<String>,
+
+    /// Fail the release if the post release hook fails, instead of only warning
+    #[arg(long, requires = "post_release_hook")]
+    hook_required: bool,
+
+    /// Release from any branch, not only from main and release branches
+    #[arg(long, requires = "release")]
+    allow_any_branch: bool,
+
+    /// GPG-sign the release tag with the key in git config user.signingkey
+    #[arg(long)]
+    sign: bool,
+
+    /// Name of the release tag, may use {major}, {minor}, {patch} and {ident}
+    /// [overrides tag_format in .grelly.toml, default: v{major}.{minor}.{patch}]
+    #[arg(long)]
+    tag_format: Option<String>,
+
+    /// Message of the release tag, may use {major}, {minor}, {patch}, {ident}, {date} and {year}
+    /// [overrides tag_message_format in .grelly.toml, default: Release {major}.{minor}.{patch}]
+    #[arg(long)]
+    tag_message_format: Option<String>,
+
+    /// Output format of the computed version
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Plain)]
+    format: OutputFormat,
+
+    /// Indent the JSON of --format json
+    #[arg(long)]
+    json_pretty: bool,
+
+    /// Prefix of the variable names in env format
+    #[arg(long, default_value = "GRELLY_")]
+    env_prefix: String,
+
+    /// Part of the version to print in plain format
+    #[arg(long, value_enum, default_value_t = Show::Full)]
+    show: Show,
+
+    /// Prepended to the printed version in plain and pep440 format, e.g. v
+    #[arg(long, default_value = "")]
+    prefix_output: String,
+
+    /// Print the version through this template instead of a format, with
+    /// {major}, {minor}, {patch}, {ident}, {commit} and {branch}; {{ and }}
+    /// are literal braces, e.g. 'build-{major}.{minor}-{commit}'
+    #[arg(long, conflicts_with_all = ["format", "show", "panoo"])]
+    template: Option<String>,
+
+    /// Explain on stderr how the version was found: branch, baseline,
+    /// distance and the version before and after the branch is applied
+    #[arg(long, conflicts_with = "release")]
+    explain: bool,
+
+    /// Do not color the --explain report, NO_COLOR in the environment does the same
+    #[arg(long)]
+    no_color: bool,
+
+    /// Write the computed version to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// How the version is wrapped in the --output file
+    #[arg(long, value_enum, default_value_t = FileFormat::Plain, requires = "output")]
+    output_format: FileFormat,
+
+    /// Print the versions of two revisions and the bump between them
+    /// (major, minor, patch or none), e.g. v1.2.0..HEAD
+    #[arg(long, value_name = "REV_A..REV_B", conflicts_with_all = ["release", "check_release", "rev"])]
+    compare: Option<String>,
+
+    /// Print the kind of the current branch (master, release, feature, fix, other
+    /// or detached), followed by the ident for feature and fix branches
+    #[arg(long, conflicts_with_all = ["release", "check_release"])]
+    print_branch_kind: bool,
+
+    /// Print the release the version counts from to stderr
+    #[arg(long)]
+    show_baseline: bool,
+
+    /// Print every tag with its target commit and the version it parses to
+    #[arg(long, conflicts_with_all = ["release", "check_release"])]
+    list_tags: bool,
+
+    /// Print only the number of commits since the last release
+    #[arg(long, conflicts_with_all = ["release", "check_release"])]
+    count_only: bool,
+
+    /// Print the nearest release reachable from HEAD, without the distance
+    #[arg(long, conflicts_with_all = ["release", "count_only", "check_release"])]
+    latest_tag: bool,
+
+    /// Print nothing, exit with 0 if HEAD is a release commit and 2 if not
+    #[arg(long, conflicts_with = "release")]
+    check_release: bool,
+
+    /// Print nothing to stdout, errors still go to stderr and --output is still written
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["verbose", "list_tags", "compare", "print_branch_kind", "count_only", "latest_tag"]
+    )]
+    quiet: bool,
+
+    /// Check grelly against a throwaway repository, print PASS or FAIL
+    #[arg(long)]
+    selftest: bool,
+
+    /// Print diagnostics to stderr, repeat for more detail (-vv), RUST_LOG takes precedence
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
 }
 
-fn version_from_string(raw_name: &str, commit: Option<&Commit>) -> Option<SemanticVersion> {
-    let re = Regex::new(r"([a-z])?(\d+)([\.\-](\d+))?([\.\-](\d+))?").unwrap();
-
-    let name = raw_name.to_lowercase();
-    let commit = commit.and_then(|c| c.as_object().short_id().ok())
-        .and_then(|b| b.as_str().and_then(|s| Some(String::from(s))));
-
-    match re.captures(&name) {
-        Some(caps) => {
-            let major = to_number(caps.get(2));
-            let minor = to_number(caps.get(4));
-            let patch = to_number(caps.get(6));
-
-            println!("caps: {:?}", caps);
-            println!("semver: {} {} {}", major, minor, patch);
-            // let minor = caps.get(2).unwrap().as_str();
-            Some(SemanticVersion::new(major, minor, patch, None, commit))
-        }
-        None => None,
-    }
+/// Parts of the --explain report
+enum Paint {
+    Label,
+    Version,
 }
 
-fn to_number(s: Option<Match>) -> usize {
-    match s {
-        Some(s) => s.as_str().parse::<usize>().unwrap_or(0),
-        None => 0,
+/// text in the color of paint, unchanged without color
+#[cfg(feature = "color")]
+fn paint(text: &str, paint: Paint, color: bool) -> String {
+    use owo_colors::OwoColorize;
+    match (color, paint) {
+        (false, _) => text.to_string(),
+        (true, Paint::Label) => text.bold().to_string(),
+        (true, Paint::Version) => text.green().bold().to_string(),
     }
 }
 
-/// a version for a commit that is a few commits (patches)
-/// away from a release version
-#[derive(Debug)]
-struct PatchVersion {
-    release: Option<SemanticVersion>,
-    patch_count: usize,
-    _patch_oid: Option<Oid>,
-    patch_short: Option<String>,
-    ident: Option<String>,
-}
-
-impl PatchVersion {
-    fn new(
-        release: SemanticVersion,
-        distance: usize,
-        ident: Option<String>,
-        oid: Option<Oid>,
-        short: Option<String>,
-    ) -> Self {
-        Self {
-            release: Some(release),
-            patch_count: distance,
-            _patch_oid: oid,
-            patch_short: short,
-            ident: ident,
-        }
-    }
-
-    fn semver(&self) -> SemanticVersion {
-        match self.release {
-            Some(ref rv) => SemanticVersion::new(
-                rv.major,
-                rv.minor,
-                rv.patch + self.patch_count,
-                self.ident.clone(),
-                self.patch_short.clone(),
-            ),
-            None => SemanticVersion::new(
-                0,
-                0,
-                self.patch_count,
-                self.ident.clone(),
-                self.patch_short.clone(),
-            ),
-        }
-    }
+#[cfg(not(feature = "color"))]
+fn paint(text: &str, _paint: Paint, _color: bool) -> String {
+    text.to_string()
 }
 
-fn head_version(repo: &Repository) -> Result<PatchVersion, VersionError> {
-    // map with all tags in the repository
-    let tagmap: HashMap<Oid, FullTag> = repo
-        .tag_names(None)?
+/// The --explain report: what the branch says, the release the
+/// history counts from, how far HEAD is from it and the version
+/// before and after the branch is applied
+fn explain(
+    repo: &Repository,
+    resolve: &ResolveOptions,
+    version: &SemanticVersion,
+    color: bool,
+) -> Result<String, VersionError> {
+    let branch = branch_version(repo, resolve)?;
+    let branch = match branch {
+        BranchVersion::Release(ref v) => format!("release {}.{}", v.major, v.minor),
+        BranchVersion::Feature(ref name)
+        | BranchVersion::Fix(ref name)
+        | BranchVersion::Other(ref name) => format!("{} {}", branch.kind(), name),
+        _ => branch.kind().to_string(),
+    };
+    let head = head_version(repo, resolve)?;
+    let baseline = match head.baseline {
+        Some(ref b) => format!(
+            "{} at {}",
+            b.source,
+            abbreviate(&repo.find_object(b.oid, None)?, resolve)?
+        ),
+        None => String::from("none, counting from the initial version"),
+    };
+    let distance = match head.patch_count {
+        1 => String::from("1 commit"),
+        n => format!("{} commits", n),
+    };
+    let lines = [
+        ("branch", branch),
+        ("baseline", baseline),
+        ("distance", distance),
+        ("history", head.semver().to_string()),
+        (
+            "version",
+            paint(&version.to_string(), Paint::Version, color),
+        ),
+    ];
+    Ok(lines
         .iter()
-        .filter_map(|n| n)
-        .filter_map(|n| {
-            if let Ok(t) = resolve_tag(repo, n) {
-                Some((t.target, t))
-            } else {
-                None
-            }
+        .map(|(label, value)| {
+            format!(
+                "{} {}\n",
+                paint(&format!("{:<9}", label), Paint::Label, color),
+                value
+            )
         })
-        .collect();
-
-    let head = repo.head()?;
-    let head_oid = head.target().ok_or(VersionError::from("no target"))?;
-    let head_short = repo
-        .find_object(head_oid, None)?
-        .short_id()?
-        .as_str()
-        .unwrap_or("0000000")
-        .to_string();
-
-    let mut revwalk = repo.revwalk()?;
-    revwalk.push_head()?;
-    revwalk.set_sorting(git2::Sort::TIME)?;
-    revwalk.simplify_first_parent()?;
-
-    let mut count = 0;
-
-    for roid in revwalk {
-        let oid = roid?;
-
-        // find the commit
-        let commit = repo.find_commit(oid)?;
-
-        // check if the commit is a release commit
-        if let Some(cm) = commit.message() {
-            if cm.to_lowercase().starts_with("release:") {
-                if let Some(rv) = version_from_string(cm, Some(&commit)) {
-                    println!(
-                        "commit-rv: {:?} {:?} {}",
-                        cm,
-                        rv,
-                        commit.as_object().short_id()?.as_str().unwrap_or("?")
-                    );
-                    return Ok(PatchVersion::new(
-                        rv,
-                        count,
-                        None,
-                        Some(head_oid),
-                        Some(head_short),
-                    ));
-                }
-            }
-        }
-
-        // check if there is a tag for that commit
-        if let Some(tag) = tagmap.get(&oid) {
-            if let Some(rv) = version_from_string(&tag.name, Some(&commit)) {
-                println!("tag-rv: {:?} {:?}", tag.name, rv);
-                return Ok(PatchVersion::new(
-                    rv,
-                    count,
-                    None,
-                    Some(head_oid),
-                    Some(head_short),
-                ));
-            }
-        }
-
-        println!("{} {}", oid, commit.summary().unwrap());
-
-        count += 1;
-        if count > 4096 {
-            return Err(VersionError::from("too many commits"));
-        }
-    }
-
-    return Ok(PatchVersion::new(
-        SemanticVersion::new(0, 0, 0, None, None),
-        count,
-        None,
-        Some(head_oid),
-        Some(head_short),
-    ));
+        .collect())
 }
 
-#[derive(Debug)]
-struct FullTag {
-    name: String,
-    target: Oid,
+/// prefix=category as in feat/=feature
+fn parse_branch_prefix(s: &str) -> Result<(String, BranchCategory), String> {
+    let (prefix, category) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected <prefix>=<category>, got '{}'", s))?;
+    let category = BranchCategory::from_str(category, true)?;
+    Ok((prefix.to_string(), category))
 }
 
-fn resolve_tag(repo: &Repository, name: &str) -> Result<FullTag, git2::Error> {
-    let tref = repo.resolve_reference_from_short_name(name)?;
-    let tag = tref.peel_to_tag()?;
-    let target = tag.target_id();
-
-    Ok(FullTag {
-        name: name.to_string(),
-        target: target,
-    })
+/// The characters between the numbers of a version, checked before
+/// the repo is opened
+fn parse_separators(s: &str) -> Result<Separators, String> {
+    Separators::new(s).map_err(|_| format!("'{}' is no set of separators", s))
 }
 
-/// Find version for current git commit.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Path to the Git Repository
-    #[arg(short, long, default_value_t = String::from("."))]
-    git: String,
-
-    #[arg(short, long)]
-    release: bool,
-
-    /// Output panoo-style versions (P12-9)
-    #[arg(short, long)]
-    panoo: bool,
+/// A date as YYYY-MM-DD, as the unix time of its start
+fn parse_since(s: &str) -> Result<i64, String> {
+    parse_iso_date(s).ok_or_else(|| format!("'{}' is no date, expected YYYY-MM-DD", s))
+}
 
+/// Log warnings by default, -v adds the details of the resolution,
+/// -vv every commit walked. RUST_LOG overrides the level.
+fn init_logger(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|buf, record| {
+            let level = match record.level() {
+                Level::Warn => "warning",
+                Level::Error => "error",
+                _ => record.target(),
+            };
+            writeln!(buf, "{}: {}", level, record.args())
+        })
+        .init();
 }
 
-/// Make a release
-fn main_release(repo: &Repository) -> Result<SemanticVersion, VersionError> {
-    let current_version = main_version(repo)?;
-    if current_version.patch == 0 {
-        eprintln!(
-            "patch version is not zero, we are already on a release commit: {}",
-            current_version.version_string()
-        );
-        return Err(VersionError::Generic(
-            "patch version is not zero".to_string(),
-        ));
+fn main_result(args: Args) -> Result<(), VersionError> {
+    let mut repo = open_repository(Path::new(&args.git), args.bare, args.work_tree.as_deref())?;
+    if let Some(ref name) = args.submodule {
+        repo = open_submodule(&repo, name)?;
     }
+    let config = Config::discover(repo.workdir().unwrap_or(repo.path()))?;
 
-    let next_version = SemanticVersion::new(
-        current_version.major,
-        current_version.minor + 1,
-        0,
-        None,
-        None,
-    );
-
-    let filename = format!("changes.{}", next_version.version_string());
-
-    let workdir = repo.workdir().ok_or(git2::Error::from_str("no workdir"))?;
-    let changes = workdir.join(&filename);
-
-    let mut cfile = File::create(&changes)?;
-    writeln!(
-        cfile,
-        "Changes for version {}",
-        next_version.version_string()
-    )?;
-    cfile.flush()?;
-
-    let obj = repo.head()?.resolve()?.peel(ObjectType::Commit)?;
-
-    let mut index = repo.index()?;
-    index.add_path(&PathBuf::from(&filename))?;
-
-    let oid = index.write_tree()?;
-    let signature = Signature::now("Peter Panoo", "peter@panoo.com")?;
-    let parent_commit = obj
-        .into_commit()
-        .map_err(|_| git2::Error::from_str("not a commit"))?;
-    let tree = repo.find_tree(oid)?;
-
-    let message = format!("release: {}", next_version.version_string());
-
-    let nexthead = repo.commit(
-        Some("HEAD"), //  point HEAD to our new commit
-        &signature,   // author
-        &signature,   // committer
-        &message,     // commit message
-        &tree,        // tree
-        &[&parent_commit],
-    )?;
+    let release_prefixes = if !args.release_prefixes.is_empty() {
+        args.release_prefixes
+    } else {
+        config
+            .release_prefixes
+            .unwrap_or_else(|| vec![String::from(DEFAULT_RELEASE_PREFIX)])
+    };
 
-    let nextobj = repo.find_object(nexthead, None)?;
+    let branch_prefixes = if !args.branch_prefixes.is_empty() {
+        args.branch_prefixes
+    } else {
+        match config.branch_prefixes {
+            Some(map) => map.into_iter().collect(),
+            None => default_branch_prefixes(),
+        }
+    };
 
-    let ident = match next_version.ident {
-        Some(ref v) => format!("-{}", v),
-        None => String::new(),
+    let main_branches = if !args.main_branches.is_empty() {
+        args.main_branches
+    } else {
+        config.main_branches.unwrap_or_else(default_main_branches)
     };
 
-    let panoo_version = format!("P{}-{}{}", next_version.major, next_version.minor, ident);
-    let panoo_message = format!("Release {}", &panoo_version);
-    repo.tag(&panoo_version, &nextobj, &signature, &panoo_message, true)?;
+    let resolve = ResolveOptions {
+        leading_zeros: args.allow_leading_zeros,
+        separators: args.separators,
+        rev: args.rev,
+        count_mode: args.count_mode,
+        skip_merges: args.skip_merges,
+        max_depth: args.max_depth,
+        since: args.since,
+        deep_search: args.deep_search,
+        max_tag_scan: args.max_tag_scan,
+        tag_prefix: args.tag_prefix,
+        ignore_tags: args.ignore_tags,
+        require_tags: args.require_tags,
+        from_tag: args.from_tag,
+        tag_message_version: args.tag_message_version,
+        initial_version: args
+            .initial_version
+            .as_deref()
+            .map(|s| SemanticVersion::parse(s, args.lenient_versions))
+            .transpose()?,
+        prefer: args.prefer,
+        short_len: args.short_len.map(usize::from),
+        full_hash: args.full_hash,
+        build_metadata: !args.no_commit_metadata,
+        dirty_suffix: args.dirty_suffix,
+        slug_branch: args.slug_branch,
+        pre_counter: args.pre_counter,
+        strict: args.strict,
+        pre: args.pre,
+        release_prefixes,
+        branch_prefixes,
+        main_branches,
+    };
 
-    Ok(next_version)
-}
+    if let Some(ref range) = args.compare {
+        let (a, b) = range.split_once("..").ok_or_else(|| {
+            VersionError::Generic(format!("expected <rev-a>..<rev-b>, got '{}'", range))
+        })?;
+        let version_of = |rev: &str| {
+            let options = ResolveOptions {
+                rev: Some(rev.to_string()),
+                ..resolve.clone()
+            };
+            head_version(&repo, &options).map(|v| v.semver())
+        };
+        let (va, vb) = (version_of(a)?, version_of(b)?);
+        println!(
+            "{}\t{}\t{}",
+            va,
+            vb,
+            bump_between(&va, &vb).map_or("none", |bump| bump.name())
+        );
+        return Ok(());
+    }
 
-fn nmerge(branch: usize, head: usize) -> Result<usize, VersionError> {
-    if branch == 0 || head == 0 {
-        Ok(head + branch)
-    } else if branch == head {
-        Ok(branch)
-    } else {
-        Err(VersionError::from("major version mismatch"))
+    if args.print_branch_kind {
+        let branch = branch_version(&repo, &resolve)?;
+        match branch {
+            BranchVersion::Feature(ref f) | BranchVersion::Fix(ref f) => {
+                println!("{} {}", branch.kind(), f)
+            }
+            _ => println!("{}", branch.kind()),
+        }
+        return Ok(());
     }
-}
 
-// fn smerge(branch: &Option<String>, head: &Option<String>) -> Option<String> {
-//     if branch.is_none() && head.is_none() {
-//         None
-//     } else if head.is_none() {
-//         branch.clone()
-//     } else if branch.is_none() {
-//         head.clone()
-//     } else {
-//         branch.clone()
-//     }
-// }
-
-/// Return a version for the current git commit.
-fn main_version(repo: &Repository) -> Result<SemanticVersion, VersionError> {
-    // check the branch itself for version information
-    let branch = branch_version(repo)?;
-    if DEBUG {
-        println!("Branch: {:?}", branch);
+    if args.list_tags {
+        for tag in list_tags(&repo, &resolve)? {
+            println!(
+                "{}\t{}\t{}",
+                tag.name,
+                tag.target
+                    .map_or_else(|| String::from("-"), |oid| oid.to_string()),
+                tag.version
+                    .map_or_else(|| String::from("unparseable"), |v| v.to_string())
+            );
+        }
+        return Ok(());
     }
 
-    let head = head_version(repo)?;
-    let headv = head.semver();
+    if args.show_baseline {
+        match head_version(&repo, &resolve)?.baseline {
+            Some(baseline) => {
+                let short = abbreviate(&repo.find_object(baseline.oid, None)?, &resolve)?;
+                eprintln!("baseline: {} ({})", short, baseline.source);
+            }
+            None => eprintln!("baseline: none"),
+        }
+    }
 
-    if DEBUG {
-        println!("Head: {:?}", head);
+    if args.latest_tag {
+        let release = latest_release(&repo, &resolve)?
+            .ok_or_else(|| VersionError::from("no release reachable from HEAD"))?;
+        println!("{}", release);
+        return Ok(());
     }
 
-    let bv = match branch {
-        BranchVersion::Master => head.semver(),
-        BranchVersion::Release(branchv) => {
-            let major = nmerge(branchv.major, headv.major)?;
-            let minor = nmerge(branchv.minor, headv.minor)?;
-            let patch = headv.patch;
+    if args.count_only {
+        println!("{}", head_version(&repo, &resolve)?.patch_count);
+        return Ok(());
+    }
 
-            SemanticVersion::new(major, minor, patch, None, headv.commit)
-        }
-        BranchVersion::Feature(f) => {
-            SemanticVersion::new(headv.major, headv.minor, headv.patch, Some(f), headv.commit)
-        }
-        BranchVersion::Fix(f) => {
-            SemanticVersion::new(headv.major, headv.minor, headv.patch, Some(f), headv.commit)
+    if args.check_release {
+        // without a release (as in a repository without commits)
+        // nothing is released, whatever the distance
+        let head = head_version(&repo, &resolve)?;
+        if head.baseline.is_none() || head.patch_count != 0 {
+            std::process::exit(EXIT_NOT_RELEASE);
         }
-        BranchVersion::Other(_f) => SemanticVersion::new(
-            headv.major,
-            headv.minor,
-            headv.patch,
-            Some(String::from("other")),
-            headv.commit,
-        ),
-    };
-
-    Ok(bv)
-}
-
-fn main_result(args: Args) -> Result<(), VersionError> {
-    let repo = Repository::open(args.git)?;
+        return Ok(());
+    }
 
     if args.release {
-        let _ = main_release(&repo).unwrap();
+        let signature = release_signature(
+            &repo,
+            args.author_name.or(config.author.name).as_deref(),
+            args.author_email.or(config.author.email).as_deref(),
+        )?;
+        let options = ReleaseOptions {
+            bump: args.bump,
+            bump_by: args.bump_by as usize,
+            dry_run: args.dry_run,
+            changes_template: args.changes_template,
+            changes_dir: args.changes_path.unwrap_or_default(),
+            changes_name: args.changes_name,
+            sign: args.sign,
+            force: args.force,
+            tag_only: args.tag_only,
+            inherit_author: args.inherit_author,
+            allow_downgrade: args.allow_downgrade,
+            post_release_hook: args.post_release_hook,
+            hook_required: args.hook_required,
+            allow_any_branch: args.allow_any_branch,
+            tag_format: args
+                .tag_format
+                .or(config.tag_format)
+                .unwrap_or_else(|| String::from(DEFAULT_TAG_FORMAT)),
+            tag_message_format: args
+                .tag_message_format
+                .or(config.tag_message_format)
+                .unwrap_or_else(|| String::from(DEFAULT_TAG_MESSAGE_FORMAT)),
+        };
+        let release = main_release(&repo, &signature, &resolve, &options)?;
+        // the plan of a dry run is output too, --quiet silences it
+        if !args.quiet {
+            if args.dry_run {
+                if let Some(ref filename) = release.changes_file {
+                    println!("changes file: {}", filename.display());
+                }
+                if let Some(ref message) = release.message {
+                    println!("commit message: {}", message);
+                }
+                println!("tag: {}", release.tag);
+            } else {
+                println!("{}{}", args.prefix_output, release.version);
+            }
+        }
     } else {
-        let v = main_version(&repo)?;
-        if args.panoo {
-            println!("{}", v.panoo_string());
+        let v = main_version(&repo, &resolve)?;
+        if args.explain {
+            let color = !args.no_color
+                && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && std::io::stderr().is_terminal();
+            eprint!("{}", explain(&repo, &resolve, &v, color)?);
+        }
+        let text = if let Some(ref template) = args.template {
+            let head = repo.head().ok();
+            let branch = head
+                .as_ref()
+                .filter(|head| head.is_branch())
+                .and_then(|head| head.shorthand())
+                .unwrap_or("");
+            render_output(template, &v, branch)?
         } else {
-            println!("{}", v.version_string());
+            match args.format {
+                OutputFormat::Env => {
+                    let p = &args.env_prefix;
+                    [
+                        format!("{}VERSION={}", p, v),
+                        format!("{}MAJOR={}", p, v.major),
+                        format!("{}MINOR={}", p, v.minor),
+                        format!("{}PATCH={}", p, v.patch),
+                        format!("{}COMMIT={}", p, v.commit.as_deref().unwrap_or("")),
+                    ]
+                    .join("\n")
+                }
+                OutputFormat::Detailed => {
+                    let distance = v.distance.unwrap_or(0);
+                    format!(
+                        "{} ({}.{}.{} + {} {})",
+                        v,
+                        v.major,
+                        v.minor,
+                        v.patch.saturating_sub(distance),
+                        distance,
+                        if distance == 1 { "commit" } else { "commits" }
+                    )
+                }
+                // what the library formats leave to the command line
+                OutputFormat::Json if args.json_pretty => render_json(&v, true)?,
+                OutputFormat::Docker
+                    if matches!(branch_version(&repo, &resolve)?, BranchVersion::Master) =>
+                {
+                    docker_tags(&v, true).join("\n")
+                }
+                OutputFormat::Plain if args.panoo => {
+                    format!("{}{}", args.prefix_output, v.panoo_string())
+                }
+                OutputFormat::Plain if args.show != Show::Full => {
+                    format!("{}{}", args.prefix_output, args.show.render(&v))
+                }
+                OutputFormat::Plain => {
+                    format!(
+                        "{}{}",
+                        args.prefix_output,
+                        render(&v, VersionFormat::Plain)?
+                    )
+                }
+                OutputFormat::Pep440 => {
+                    format!(
+                        "{}{}",
+                        args.prefix_output,
+                        render(&v, VersionFormat::Pep440)?
+                    )
+                }
+                OutputFormat::Json => render(&v, VersionFormat::Json)?,
+                OutputFormat::Docker => render(&v, VersionFormat::Docker)?,
+            }
+        };
+        match args.output {
+            Some(path) => write_version_file(&path, &text, args.output_format)?,
+            None if args.quiet => {}
+            None => println!("{}", text),
         }
     }
 
-    // let stats = repo.statuses(None).unwrap();
-
-    // stats.iter().for_each(|s| {
-    //     println!("state: {:?}", s.status());
-    // });
-
     // let _ = main_version(&repo).unwrap();
 
     //     print_last_100_commits(&repo).unwrap();
@@ -482,8 +781,79 @@ fn main_result(args: Args) -> Result<(), VersionError> {
     Ok(())
 }
 
+/// Make a few commits and a release in a temporary repository and
+/// compare the versions with the expected ones. True if all pass.
+fn selftest() -> Result<bool, VersionError> {
+    let dir = tempfile::TempDir::new()?;
+    let mut init = RepositoryInitOptions::new();
+    init.initial_head("main");
+    let repo = Repository::init_opts(dir.path(), &init)?;
+    let signature = Signature::now("grelly selftest", "selftest@localhost")?;
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+
+    let commit = |message: &str| -> Result<(), VersionError> {
+        let parent = repo.head().ok().map(|h| h.peel_to_commit()).transpose()?;
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        Ok(())
+    };
+
+    let mut passed = true;
+    let mut check = |what: &str, v: SemanticVersion, expected: &str| {
+        let found = format!("{}.{}.{}", v.major, v.minor, v.patch);
+        if found == expected {
+            println!("PASS: {} is {}", what, found);
+        } else {
+            println!("FAIL: {} is {}, expected {}", what, found, expected);
+            passed = false;
+        }
+    };
+
+    let resolve = ResolveOptions::default();
+    commit("initial")?;
+    check(
+        "unreleased history",
+        main_version(&repo, &resolve)?,
+        "0.0.1",
+    );
+
+    commit("release: 1.2.0")?;
+    commit("one")?;
+    commit("two")?;
+    check(
+        "two commits after 1.2.0",
+        main_version(&repo, &resolve)?,
+        "1.2.2",
+    );
+
+    let release = main_release(&repo, &signature, &resolve, &ReleaseOptions::default())?;
+    check("release", release.version, "1.3.0");
+    check("release commit", main_version(&repo, &resolve)?, "1.3.0");
+
+    Ok(passed)
+}
+
 fn main() {
     let args = Args::parse();
+    init_logger(args.verbose);
+
+    if args.selftest {
+        match selftest() {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("FAIL: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     if let Err(e) = main_result(args) {
         eprintln!("error: {}", e);
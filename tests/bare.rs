@@ -0,0 +1,50 @@
+mod common;
+
+use common::TestRepo;
+use git2::build::RepoBuilder;
+use grelly::{
+    main_release, main_version, open_repository, ReleaseOptions, ResolveOptions, VersionError,
+};
+use tempfile::TempDir;
+
+fn bare_clone(t: &TestRepo) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    RepoBuilder::new()
+        .bare(true)
+        .clone(t.path().to_str().unwrap(), dir.path())
+        .unwrap();
+    dir
+}
+
+#[test]
+fn bare_repo_resolves() {
+    let t = TestRepo::new();
+    t.commit("release: 2.3.0");
+    t.commit("one");
+    let dir = bare_clone(&t);
+
+    let repo = open_repository(dir.path(), true, None).unwrap();
+    assert!(repo.is_bare());
+
+    let v = main_version(&repo, &ResolveOptions::default()).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (2, 3, 1));
+}
+
+#[test]
+fn bare_repo_refuses_release() {
+    let t = TestRepo::new();
+    t.commit("release: 2.3.0");
+    t.commit("one");
+    let dir = bare_clone(&t);
+
+    let repo = open_repository(dir.path(), true, None).unwrap();
+    let signature = t.signature();
+    let err = main_release(
+        &repo,
+        &signature,
+        &ResolveOptions::default(),
+        &ReleaseOptions::default(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, VersionError::Generic(ref m) if m.contains("bare")));
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{
+    branch_version, main_version, parse_semver, resolve_version, BranchVersion, ResolveOptions,
+    SemanticVersion,
+};
+
+fn branch_kind(name: &str) -> BranchVersion {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.branch(name);
+    branch_version(&t.repo, &ResolveOptions::default()).unwrap()
+}
+
+#[test]
+fn number_inside_feature_branch_is_not_a_release() {
+    match branch_kind("feature/some-2-thing") {
+        BranchVersion::Feature(f) => assert_eq!(f, "some-2-thing"),
+        other => panic!("expected Feature, got {:?}", other),
+    }
+}
+
+#[test]
+fn version_branch_is_a_release() {
+    match branch_kind("1.2.3") {
+        BranchVersion::Release(v) => {
+            assert_eq!((v.major, v.minor, v.patch), (1, 2, 3));
+        }
+        other => panic!("expected Release, got {:?}", other),
+    }
+}
+
+#[test]
+fn detached_head_resolves() {
+    let t = TestRepo::new();
+    let release = t.commit("release: 1.4.0");
+    t.commit("one");
+    t.commit("two");
+    t.repo.set_head_detached(release).unwrap();
+
+    match branch_version(&t.repo, &ResolveOptions::default()).unwrap() {
+        BranchVersion::Detached => {}
+        other => panic!("expected Detached, got {:?}", other),
+    }
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 4, 0));
+}
+
+#[test]
+fn non_ascii_feature_branch() {
+    match branch_kind("feature/ünïcödé") {
+        BranchVersion::Feature(f) => assert_eq!(f, "ünïcödé"),
+        other => panic!("expected Feature, got {:?}", other),
+    }
+}
+
+#[test]
+fn non_ascii_fix_branch() {
+    match branch_kind("fix/ß-ü") {
+        BranchVersion::Fix(f) => assert_eq!(f, "ß-ü"),
+        other => panic!("expected Fix, got {:?}", other),
+    }
+}
+
+#[test]
+fn gitflow_release_branch() {
+    match branch_kind("release/1.2") {
+        BranchVersion::Release(v) => {
+            assert_eq!((v.major, v.minor, v.patch), (1, 2, 0));
+        }
+        other => panic!("expected Release, got {:?}", other),
+    }
+}
+
+#[test]
+fn gitflow_release_branch_constrains_head() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("release/1.2");
+    t.commit("one");
+    t.commit("two");
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 2, 2));
+
+    t.branch("release/1.3");
+    assert!(resolve_version(&t.repo).is_err());
+}
+
+#[test]
+fn feature_ident_counts_commits() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/foo");
+    t.commit("one");
+    t.commit("two");
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!(v.ident.as_deref(), Some("foo.2"));
+
+    let options = ResolveOptions {
+        pre_counter: false,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!(v.ident.as_deref(), Some("foo"));
+}
+
+/// Ident of a release branch on top of the given release commit
+fn release_ident(branch: &str, release: &str) -> Option<String> {
+    let t = TestRepo::new();
+    let head = t.commit("initial");
+    t.tag(release, head);
+    t.branch(branch);
+    resolve_version(&t.repo).unwrap().ident
+}
+
+#[test]
+fn release_branch_without_idents() {
+    assert_eq!(release_ident("1.2.0", "v1.2.0"), None);
+}
+
+#[test]
+fn release_branch_ident_is_kept() {
+    assert_eq!(release_ident("1.2.0-rc", "v1.2.0").as_deref(), Some("rc"));
+}
+
+#[test]
+fn release_ident_is_kept_on_plain_branch() {
+    assert_eq!(
+        release_ident("1.2.0", "v1.2.0-beta").as_deref(),
+        Some("beta")
+    );
+}
+
+#[test]
+fn branch_ident_wins_over_release_ident() {
+    assert_eq!(
+        release_ident("1.2.0-rc", "v1.2.0-beta").as_deref(),
+        Some("rc")
+    );
+    assert_eq!(
+        release_ident("1.2.0-rc", "v1.2.0-rc").as_deref(),
+        Some("rc")
+    );
+}
+
+#[test]
+fn slug_of_nested_branch() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/Team/foo_bar");
+    t.commit("one");
+
+    let options = ResolveOptions {
+        slug_branch: true,
+        pre_counter: false,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!(v.ident.as_deref(), Some("team-foo-bar"));
+}
+
+/// Version string without build metadata of a feature branch
+fn nested_version(branch: &str) -> String {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch(branch);
+    t.commit("one");
+    let options = ResolveOptions {
+        build_metadata: false,
+        ..ResolveOptions::default()
+    };
+    main_version(&t.repo, &options).unwrap().to_string()
+}
+
+#[test]
+fn two_level_feature_branch() {
+    let v = nested_version("feature/team/awesome");
+    assert_eq!(v, "1.2.1-team-awesome.1");
+    assert!(parse_semver(&v).is_some());
+}
+
+#[test]
+fn three_level_feature_branch() {
+    let v = nested_version("feature/team/sub/awesome");
+    assert_eq!(v, "1.2.1-team-sub-awesome.1");
+    assert!(parse_semver(&v).is_some());
+}
+
+#[test]
+fn feature_branch_ident_is_sanitized() {
+    for (branch, ident) in [
+        ("feature/foo_bar", "foo-bar.1"),
+        ("feature/grüße", "gr-e.1"),
+    ] {
+        let t = TestRepo::new();
+        t.commit("release: 1.2.0");
+        t.branch(branch);
+        t.commit("one");
+        let v = main_version(&t.repo, &ResolveOptions::default()).unwrap();
+        assert_eq!(v.ident.as_deref(), Some(ident), "{}", branch);
+        assert!(v.to_string().parse::<SemanticVersion>().is_ok());
+    }
+}
+
+#[test]
+fn configured_main_branch() {
+    let options = ResolveOptions {
+        main_branches: vec![String::from("trunk")],
+        ..ResolveOptions::default()
+    };
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.branch("trunk");
+    match branch_version(&t.repo, &options).unwrap() {
+        BranchVersion::Master => {}
+        other => panic!("expected Master, got {:?}", other),
+    }
+
+    t.branch("main");
+    match branch_version(&t.repo, &options).unwrap() {
+        BranchVersion::Other(name) => assert_eq!(name, "main"),
+        other => panic!("expected Other, got {:?}", other),
+    }
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use common::TestRepo;
+use std::process::Command;
+
+fn grelly(t: &TestRepo, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_grelly"))
+        .arg("--git")
+        .arg(t.path())
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn stdout_is_only_the_version_even_when_verbose() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let head = t.commit("one");
+
+    let out = grelly(&t, &["-vv"]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(stdout, format!("1.2.1+{}\n", t.short_id(head)));
+    // the diagnostics went to stderr
+    assert!(!out.stderr.is_empty());
+}
+
+#[test]
+fn selftest_passes() {
+    let out = Command::new(env!("CARGO_BIN_EXE_grelly"))
+        .arg("--selftest")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.lines().all(|l| l.starts_with("PASS: ")));
+}
+
+#[test]
+fn prefix_output_is_only_printed() {
+    let t = TestRepo::new();
+    let head = t.commit("initial");
+    t.tag("v1.2.0", head);
+
+    let out = grelly(&t, &["--prefix-output", "v", "--no-commit-metadata"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "v1.2.0\n");
+
+    let file = t.path().join("VERSION");
+    let out = Command::new(env!("CARGO_BIN_EXE_grelly"))
+        .arg("--git")
+        .arg(t.path())
+        .args(["--prefix-output", "v", "--show", "major.minor", "--output"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "v1.2\n");
+
+    // the tag of a release is unaffected
+    t.commit("one");
+    let out = grelly(&t, &["--prefix-output", "version-", "--release"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "version-1.3.0\n");
+    assert!(t.repo.find_reference("refs/tags/v1.3.0").is_ok());
+}
+
+#[test]
+fn template_renders_branch_and_commit() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let head = t.commit("one");
+
+    let out = grelly(
+        &t,
+        &["--template", "{branch}: {{{major}.{minor}}}-{commit}"],
+    );
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("master: {{1.2}}-{}\n", t.short_id(head))
+    );
+}
+
+#[test]
+fn explain_reports_on_stderr() {
+    let t = TestRepo::new();
+    let release = t.commit("initial");
+    t.tag("v1.2.0", release);
+    t.branch("feature/foo");
+    let head = t.commit("one");
+
+    let out = grelly(&t, &["--explain", "--no-color"]);
+    assert!(out.status.success());
+    let version = format!("1.2.1-foo.1+{}", t.short_id(head));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("{}\n", version)
+    );
+    let report = String::from_utf8(out.stderr).unwrap();
+    assert!(report.contains("branch    feature foo\n"), "{}", report);
+    assert!(report.contains(&format!(
+        "baseline  tag v1.2.0 at {}\n",
+        t.short_id(release)
+    )));
+    assert!(report.contains("distance  1 commit\n"), "{}", report);
+    assert!(report.contains(&format!("history   1.2.1+{}\n", t.short_id(head))));
+    assert!(report.contains(&format!("version   {}\n", version)));
+    assert!(!report.contains('\x1b'));
+}
+
+#[test]
+fn quiet_silences_the_dry_run_plan() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let out = grelly(&t, &["--release", "--dry-run"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .contains("tag: v1.3.0"));
+
+    let out = grelly(&t, &["--release", "--dry-run", "--quiet"]);
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn json_and_pep440_use_the_sanitized_ident() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/team/foo_bar");
+    t.commit("one");
+
+    let out = grelly(&t, &["--format", "json", "--no-commit-metadata"]);
+    assert!(out.status.success());
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(doc["ident"], "team-foo-bar.1");
+    assert_eq!(doc["version"], "1.2.1-team-foo-bar.1");
+
+    let out = grelly(&t, &["--format", "pep440"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "1.2.1.dev1+team.foo.bar.1\n"
+    );
+
+    // a dirty suffix is no valid ident as given either
+    std::fs::write(t.path().join("file.txt"), "changed").unwrap();
+    let out = grelly(
+        &t,
+        &[
+            "--format",
+            "json",
+            "--dirty-suffix",
+            "_dirty",
+            "--no-commit-metadata",
+        ],
+    );
+    assert!(out.status.success());
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(doc["ident"], "team-foo-bar.1-dirty");
+}
+
+#[test]
+fn lenient_versions_accepts_a_short_initial_version() {
+    let t = TestRepo::new();
+    t.commit("initial");
+
+    let out = grelly(&t, &["--initial-version", "v1.2", "--no-commit-metadata"]);
+    assert!(!out.status.success());
+
+    let out = grelly(
+        &t,
+        &[
+            "--initial-version",
+            "v1.2",
+            "--lenient-versions",
+            "--no-commit-metadata",
+        ],
+    );
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "1.2.1\n");
+}
+
+#[test]
+fn rev_ignores_the_checked_out_branch() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let one = t.commit("one");
+    t.branch("feature/foo");
+    t.commit("two");
+
+    let out = grelly(&t, &["--rev", "HEAD~1"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("1.2.1+{}\n", t.short_id(one))
+    );
+
+    // a rev that names a branch gets that branch's version
+    let out = grelly(&t, &["--rev", "feature/foo", "--no-commit-metadata"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "1.2.2-foo.2\n");
+}
+
+#[test]
+fn check_release_fails_without_a_release() {
+    let t = TestRepo::new();
+    let out = grelly(&t, &["--check-release"]);
+    assert_eq!(out.status.code(), Some(2));
+
+    t.commit("initial");
+    let out = grelly(&t, &["--check-release"]);
+    assert_eq!(out.status.code(), Some(2));
+
+    t.commit("release: 1.0.0");
+    let out = grelly(&t, &["--check-release"]);
+    assert_eq!(out.status.code(), Some(0));
+}
+
+#[test]
+fn quiet_conflicts_with_latest_tag() {
+    let t = TestRepo::new();
+    let head = t.commit("initial");
+    t.tag("v1.2.0", head);
+
+    let out = grelly(&t, &["--latest-tag", "--quiet"]);
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+}
@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+use git2::{Oid, Repository, Signature, Time};
+use std::{cell::Cell, fs, path::Path};
+use tempfile::TempDir;
+
+/// A throwaway repository in a temp dir. Every commit gets a
+/// timestamp one minute after the previous one, so time-sorted
+/// walks are deterministic.
+pub struct TestRepo {
+    pub dir: TempDir,
+    pub repo: Repository,
+    clock: Cell<i64>,
+    step: Cell<i64>,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "Test User").unwrap();
+            config.set_str("user.email", "test@example.com").unwrap();
+        }
+        Self {
+            dir,
+            repo,
+            clock: Cell::new(1_700_000_000),
+            step: Cell::new(60),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Give all following commits the same timestamp, like a
+    /// scripted import or a fast rebase would
+    pub fn freeze_clock(&self) {
+        self.step.set(0);
+    }
+
+    pub fn signature(&self) -> Signature<'static> {
+        let time = self.clock.get() + self.step.get();
+        self.clock.set(time);
+        Signature::new("Test User", "test@example.com", &Time::new(time, 0)).unwrap()
+    }
+
+    /// Commit a change to `file.txt` on top of HEAD
+    pub fn commit(&self, message: &str) -> Oid {
+        self.commit_file("file.txt", message)
+    }
+
+    /// Commit a change to the given file on top of HEAD
+    pub fn commit_file(&self, name: &str, message: &str) -> Oid {
+        let path = self.path().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, message).unwrap();
+
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let parents: Vec<_> = match self.repo.head() {
+            Ok(head) => vec![head.peel_to_commit().unwrap()],
+            Err(_) => vec![],
+        };
+        let parents: Vec<_> = parents.iter().collect();
+        let sig = self.signature();
+        self.repo
+            .commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    /// Merge commit of HEAD and other, keeping the tree of HEAD
+    pub fn merge(&self, other: Oid, message: &str) -> Oid {
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        let other = self.repo.find_commit(other).unwrap();
+        let tree = head.tree().unwrap();
+        let sig = self.signature();
+        self.repo
+            .commit(Some("HEAD"), &sig, &sig, message, &tree, &[&head, &other])
+            .unwrap()
+    }
+
+    /// Create a branch at HEAD and check it out
+    pub fn branch(&self, name: &str) {
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        self.repo.branch(name, &head, true).unwrap();
+        self.checkout(name);
+    }
+
+    /// Point HEAD at an existing branch
+    pub fn checkout(&self, name: &str) {
+        self.repo.set_head(&format!("refs/heads/{}", name)).unwrap();
+        self.repo
+            .checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+    }
+
+    /// Annotated tag on the given commit
+    pub fn tag(&self, name: &str, target: Oid) {
+        let obj = self.repo.find_object(target, None).unwrap();
+        let sig = self.signature();
+        self.repo.tag(name, &obj, &sig, name, false).unwrap();
+    }
+
+    /// Lightweight tag, a plain ref to the given commit
+    pub fn lightweight_tag(&self, name: &str, target: Oid) {
+        let obj = self.repo.find_object(target, None).unwrap();
+        self.repo.tag_lightweight(name, &obj, false).unwrap();
+    }
+
+    pub fn short_id(&self, oid: Oid) -> String {
+        let obj = self.repo.find_object(oid, None).unwrap();
+        obj.short_id().unwrap().as_str().unwrap().to_string()
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{main_version, ResolveOptions};
+use std::{fs, path::Path};
+
+fn dirty_options() -> ResolveOptions {
+    ResolveOptions {
+        dirty_suffix: String::from("-dirty"),
+        ..ResolveOptions::default()
+    }
+}
+
+#[test]
+fn clean_tree_has_no_suffix() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let v = main_version(&t.repo, &dirty_options()).unwrap();
+    assert_eq!(v.ident, None);
+}
+
+#[test]
+fn staged_change_adds_suffix() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    fs::write(t.path().join("file.txt"), "changed").unwrap();
+    let mut index = t.repo.index().unwrap();
+    index.add_path(Path::new("file.txt")).unwrap();
+    index.write().unwrap();
+
+    let v = main_version(&t.repo, &dirty_options()).unwrap();
+    assert_eq!(v.ident.as_deref(), Some("dirty"));
+    assert!(v.to_string().starts_with("1.2.1-dirty+"));
+
+    // without a suffix the tree is not checked
+    let v = main_version(&t.repo, &ResolveOptions::default()).unwrap();
+    assert_eq!(v.ident, None);
+}
+
+#[test]
+fn suffix_is_appended_to_branch_ident() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/login");
+    t.commit("one");
+
+    fs::write(t.path().join("file.txt"), "changed").unwrap();
+
+    let v = main_version(&t.repo, &dirty_options()).unwrap();
+    assert_eq!(v.ident.as_deref(), Some("login.1-dirty"));
+}
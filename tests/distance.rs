@@ -0,0 +1,257 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{
+    head_version, latest_release, main_version, resolve_version, CountMode, ResolveOptions,
+};
+
+fn patch(t: &TestRepo) -> usize {
+    resolve_version(&t.repo).unwrap().patch
+}
+
+#[test]
+fn release_commit_at_head_is_patch_zero() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("release: 1.2.0");
+    assert_eq!(patch(&t), 0);
+}
+
+#[test]
+fn three_commits_after_release() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("release: 1.2.0");
+    t.commit("one");
+    t.commit("two");
+    t.commit("three");
+    assert_eq!(patch(&t), 3);
+}
+
+#[test]
+fn merge_counts_once_on_first_parent_line() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("release: 1.2.0");
+    t.branch("feature/side");
+    t.commit_file("side.txt", "side one");
+    let side = t.commit_file("side.txt", "side two");
+    t.checkout("master");
+    t.commit("main one");
+    t.merge(side, "merge feature/side");
+    t.commit("main two");
+
+    // main one, the merge, main two
+    assert_eq!(patch(&t), 3);
+}
+
+#[test]
+fn equal_commit_times_keep_history_order() {
+    let t = TestRepo::new();
+    t.freeze_clock();
+    t.commit("c1");
+    let c2 = t.commit("c2");
+    t.tag("v1.2.0", c2);
+    t.commit("c3");
+    assert_eq!(patch(&t), 1);
+}
+
+fn merged_repo() -> TestRepo {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("release: 1.2.0");
+    t.branch("feature/side");
+    t.commit_file("side.txt", "side one");
+    let side = t.commit_file("side.txt", "side two");
+    t.checkout("master");
+    t.commit("main one");
+    t.merge(side, "merge feature/side");
+    t
+}
+
+fn patch_in(t: &TestRepo, count_mode: CountMode) -> usize {
+    let options = ResolveOptions {
+        count_mode,
+        ..ResolveOptions::default()
+    };
+    main_version(&t.repo, &options).unwrap().patch
+}
+
+#[test]
+fn first_parent_skips_merged_commits() {
+    let t = merged_repo();
+    // main one, the merge
+    assert_eq!(patch_in(&t, CountMode::FirstParent), 2);
+}
+
+#[test]
+fn all_counts_merged_commits() {
+    let t = merged_repo();
+    // main one, side one, side two, the merge
+    assert_eq!(patch_in(&t, CountMode::All), 4);
+}
+
+#[test]
+fn initial_version_is_the_baseline_without_release() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("one");
+    let options = ResolveOptions {
+        initial_version: Some("0.1.0".parse().unwrap()),
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (0, 1, 2));
+}
+
+#[test]
+fn short_len_extends_the_commit_id() {
+    let t = TestRepo::new();
+    t.commit("release: 1.0.0");
+    let head = t.commit("one");
+    let options = ResolveOptions {
+        short_len: Some(12),
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    let expected = &head.to_string()[..12];
+    assert_eq!(v.commit.as_deref(), Some(expected));
+    assert_eq!(v.build.as_deref(), Some(expected));
+}
+
+#[test]
+fn full_hash_uses_the_whole_commit_id() {
+    let t = TestRepo::new();
+    t.commit("release: 1.0.0");
+    let head = t.commit("one");
+    let options = ResolveOptions {
+        full_hash: true,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    let expected = head.to_string();
+    assert_eq!(v.build.as_deref(), Some(expected.as_str()));
+    assert_eq!(v.to_string(), format!("1.0.1+{}", expected));
+}
+
+#[test]
+fn same_commit_id_for_commit_and_tag_releases() {
+    let options = ResolveOptions {
+        short_len: Some(12),
+        ..ResolveOptions::default()
+    };
+
+    let by_commit = TestRepo::new();
+    by_commit.commit("initial");
+    by_commit.commit("release: 1.0.0");
+
+    let by_tag = TestRepo::new();
+    by_tag.commit("initial");
+    let head = by_tag.commit("one");
+    by_tag.tag("v1.0.0", head);
+
+    for t in [by_commit, by_tag] {
+        let head = main_version(&t.repo, &options).unwrap();
+        let release = latest_release(&t.repo, &options).unwrap().unwrap();
+        assert_eq!(head.commit.as_ref().map(String::len), Some(12));
+        assert_eq!(release.commit, head.commit);
+    }
+}
+
+#[test]
+fn deep_search_finds_tag_on_merged_branch() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.branch("feature/side");
+    let tagged = t.commit_file("side.txt", "side one");
+    t.tag("v2.1.0", tagged);
+    let side = t.commit_file("side.txt", "side two");
+    t.checkout("master");
+    t.commit("main one");
+    t.merge(side, "merge feature/side");
+
+    // the first-parent line never reaches the tag
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor), (0, 0));
+
+    let options = ResolveOptions {
+        deep_search: true,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    // the merge, main one and side two
+    assert_eq!((v.major, v.minor, v.patch), (2, 1, 3));
+}
+
+#[test]
+fn repository_without_commits_is_zero() {
+    let t = TestRepo::new();
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!(v.to_string(), "0.0.0");
+}
+
+#[test]
+fn skip_merges_counts_only_real_commits() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("release: 1.2.0");
+    for i in 0..3 {
+        t.branch(&format!("feature/f{}", i));
+        let side = t.commit_file("side.txt", &format!("side {}", i));
+        t.checkout("master");
+        t.commit(&format!("main {}", i));
+        t.merge(side, &format!("merge f{}", i));
+    }
+
+    let options = ResolveOptions {
+        count_mode: CountMode::All,
+        skip_merges: true,
+        ..ResolveOptions::default()
+    };
+    // three main and three side commits, no merges
+    assert_eq!(main_version(&t.repo, &options).unwrap().patch, 6);
+
+    let options = ResolveOptions {
+        skip_merges: true,
+        ..ResolveOptions::default()
+    };
+    // only the main commits on the first-parent line
+    assert_eq!(main_version(&t.repo, &options).unwrap().patch, 3);
+    assert_eq!(patch(&t), 6);
+}
+
+#[test]
+fn baseline_is_recorded() {
+    let t = TestRepo::new();
+    let release = t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let head = head_version(&t.repo, &ResolveOptions::default()).unwrap();
+    let baseline = head.baseline.unwrap();
+    assert_eq!(baseline.oid, release);
+    assert_eq!(baseline.source, "commit release: 1.2.0");
+
+    let tagged = t.commit("two");
+    t.tag("v1.3.0", tagged);
+    t.commit("three");
+    let head = head_version(&t.repo, &ResolveOptions::default()).unwrap();
+    assert_eq!(head.baseline.unwrap().source, "tag v1.3.0");
+}
+
+#[test]
+fn since_cuts_off_older_history() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let first = t.commit("one");
+    t.commit("two");
+    t.commit("three");
+
+    let since = t.repo.find_commit(first).unwrap().time().seconds();
+    let options = ResolveOptions {
+        since: Some(since),
+        ..ResolveOptions::default()
+    };
+    // the release is older than the cutoff, so there is none
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (0, 0, 3));
+}
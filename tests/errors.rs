@@ -0,0 +1,44 @@
+use git2::{ErrorClass, ErrorCode};
+use grelly::VersionError;
+use std::io;
+
+#[test]
+fn git_errors_keep_their_code_and_class() {
+    let err = VersionError::from(git2::Error::new(
+        ErrorCode::NotFound,
+        ErrorClass::Reference,
+        "reference 'refs/tags/v9' not found",
+    ));
+    assert!(err.is_not_found());
+    assert!(!err.is_exists());
+    assert_eq!(err.git_code(), Some(ErrorCode::NotFound));
+    assert_eq!(err.git_class(), Some(ErrorClass::Reference));
+
+    let err = VersionError::from(git2::Error::new(
+        ErrorCode::Exists,
+        ErrorClass::Tag,
+        "tag already exists",
+    ));
+    assert!(err.is_exists());
+    assert!(!err.is_not_found());
+}
+
+#[test]
+fn io_errors_are_classified_by_kind() {
+    let err = VersionError::from(io::Error::from(io::ErrorKind::PermissionDenied));
+    assert!(err.is_permission_denied());
+    assert!(!err.is_not_found());
+    assert_eq!(err.git_code(), None);
+
+    let err = VersionError::from(io::Error::from(io::ErrorKind::NotFound));
+    assert!(err.is_not_found());
+}
+
+#[test]
+fn other_errors_match_nothing() {
+    let err = VersionError::from("no target");
+    assert!(!err.is_not_found());
+    assert!(!err.is_exists());
+    assert!(!err.is_permission_denied());
+    assert_eq!(err.git_class(), None);
+}
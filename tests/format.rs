@@ -0,0 +1,85 @@
+use grelly::{docker_tags, render, render_json, SemanticVersion, VersionFormat};
+
+fn release() -> SemanticVersion {
+    let mut v = SemanticVersion::new(1, 2, 3, None, None, Some(String::from("a1b2c3d")));
+    v.distance = Some(0);
+    v
+}
+
+fn prerelease() -> SemanticVersion {
+    let mut v = SemanticVersion::new(1, 2, 3, Some(String::from("rc.1")), None, None);
+    v.distance = Some(2);
+    v
+}
+
+#[test]
+fn plain() {
+    assert_eq!(
+        render(&release(), VersionFormat::Plain).unwrap(),
+        "1.2.3+a1b2c3d"
+    );
+    assert_eq!(
+        render(&prerelease(), VersionFormat::Plain).unwrap(),
+        "1.2.3-rc.1"
+    );
+}
+
+#[test]
+fn json() {
+    let json = render(&release(), VersionFormat::Json).unwrap();
+    let doc: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(doc["schema"], 1);
+    assert_eq!(doc["version"], "1.2.3+a1b2c3d");
+    assert_eq!(doc["major"], 1);
+    assert_eq!(doc["build"], "a1b2c3d");
+    assert!(!json.contains('\n'));
+    assert!(render_json(&release(), true).unwrap().contains('\n'));
+}
+
+#[test]
+fn pep440() {
+    assert_eq!(render(&release(), VersionFormat::Pep440).unwrap(), "1.2.3");
+    assert_eq!(
+        render(&prerelease(), VersionFormat::Pep440).unwrap(),
+        "1.2.3rc1.dev2"
+    );
+}
+
+#[test]
+fn docker() {
+    assert_eq!(
+        render(&release(), VersionFormat::Docker).unwrap(),
+        "1.2.3\n1.2\n1"
+    );
+    assert_eq!(
+        render(&prerelease(), VersionFormat::Docker).unwrap(),
+        "1.2.3-rc.1"
+    );
+    assert_eq!(
+        docker_tags(&release(), true),
+        ["1.2.3", "1.2", "1", "latest"]
+    );
+    assert_eq!(docker_tags(&prerelease(), true), ["1.2.3-rc.1"]);
+}
+
+#[test]
+fn semver_interop() {
+    let v = release().to_semver();
+    assert_eq!(v, semver::Version::parse("1.2.3+a1b2c3d").unwrap());
+
+    let v = prerelease().to_semver();
+    assert_eq!(v.pre.as_str(), "rc.1");
+    assert!(v.build.is_empty());
+    assert_eq!(v.to_string(), prerelease().to_string());
+
+    let odd = SemanticVersion::new(1, 0, 0, Some(String::from("team/foo_bar")), None, None);
+    assert_eq!(odd.to_semver().to_string(), "1.0.0-team-foo-bar");
+}
+
+#[test]
+fn semver_keeps_build_metadata_display_prints() {
+    let v = SemanticVersion::new(1, 2, 3, None, None, Some(String::from("a1b2c3d_wip")));
+    assert_eq!(v.to_string(), "1.2.3+a1b2c3d-wip");
+    assert_eq!(v.to_semver().to_string(), v.to_string());
+    assert_eq!(v.to_semver().build.as_str(), "a1b2c3d-wip");
+}
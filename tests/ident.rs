@@ -0,0 +1,37 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{parse_semver, resolve_version, sanitize_ident};
+
+#[test]
+fn valid_idents_are_unchanged() {
+    assert_eq!(sanitize_ident("rc.1"), "rc.1");
+    assert_eq!(sanitize_ident("beta-2.x-y"), "beta-2.x-y");
+    assert_eq!(sanitize_ident("0"), "0");
+}
+
+#[test]
+fn slashes_and_underscores_become_hyphens() {
+    assert_eq!(sanitize_ident("team/foo_bar"), "team-foo-bar");
+    assert_eq!(sanitize_ident("a__b//c"), "a-b-c");
+}
+
+#[test]
+fn empty_identifiers_and_leading_zeros() {
+    assert_eq!(sanitize_ident("rc..007"), "rc.7");
+    assert_eq!(sanitize_ident("00"), "0");
+    assert_eq!(sanitize_ident(".."), "");
+}
+
+#[test]
+fn branch_with_underscores_renders_valid_semver() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/my_team/new_thing");
+    t.commit("one");
+
+    let mut v = resolve_version(&t.repo).unwrap();
+    v.build = None;
+    assert_eq!(v.to_string(), "1.2.1-my-team-new-thing.1");
+    assert!(parse_semver(&v.to_string()).is_some());
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{main_version, ResolveOptions, NOTES_REF};
+
+fn note(t: &TestRepo, oid: git2::Oid, text: &str) {
+    let sig = t.signature();
+    t.repo
+        .note(&sig, &sig, Some(NOTES_REF), oid, text, false)
+        .unwrap();
+}
+
+#[test]
+fn note_pins_the_version() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/foo");
+    let head = t.commit("one");
+    note(&t, head, "3.0.0-pinned\n");
+
+    let v = main_version(&t.repo, &ResolveOptions::default()).unwrap();
+    assert_eq!(v.to_string(), format!("3.0.0-pinned+{}", t.short_id(head)));
+    assert_eq!(v.commit, Some(t.short_id(head)));
+}
+
+#[test]
+fn options_apply_to_a_pinned_version() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let head = t.commit("one");
+    note(&t, head, "3.0.0");
+
+    let options = ResolveOptions {
+        pre: Some(String::from("rc.1")),
+        build_metadata: false,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!(v.to_string(), "3.0.0-rc.1");
+
+    std::fs::write(t.path().join("file.txt"), "changed").unwrap();
+    let options = ResolveOptions {
+        dirty_suffix: String::from("-dirty"),
+        build_metadata: false,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!(v.to_string(), "3.0.0-dirty");
+}
+
+#[test]
+fn note_on_another_commit_is_ignored() {
+    let t = TestRepo::new();
+    let old = t.commit("release: 1.2.0");
+    t.commit("one");
+    note(&t, old, "3.0.0");
+
+    let v = main_version(&t.repo, &ResolveOptions::default()).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 2, 1));
+}
+
+#[test]
+fn note_that_is_no_version_is_ignored() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let head = t.commit("one");
+    note(&t, head, "built on ci-7");
+
+    let v = main_version(&t.repo, &ResolveOptions::default()).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 2, 1));
+}
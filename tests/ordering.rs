@@ -0,0 +1,58 @@
+use grelly::{bump_between, Bump, SemanticVersion};
+
+fn version(major: usize, minor: usize, patch: usize, ident: Option<&str>) -> SemanticVersion {
+    SemanticVersion::new(major, minor, patch, ident.map(String::from), None, None)
+}
+
+#[test]
+fn prerelease_is_lower_than_release() {
+    assert!(version(1, 0, 0, Some("alpha")) < version(1, 0, 0, None));
+}
+
+#[test]
+fn components_compare_numerically() {
+    assert!(version(1, 2, 0, None) < version(1, 10, 0, None));
+    assert!(version(1, 9, 9, None) < version(2, 0, 0, None));
+    assert!(version(1, 0, 2, None) < version(1, 0, 10, None));
+}
+
+#[test]
+fn prerelease_fields_follow_semver_precedence() {
+    assert!(version(1, 0, 0, Some("alpha")) < version(1, 0, 0, Some("alpha.1")));
+    assert!(version(1, 0, 0, Some("alpha.1")) < version(1, 0, 0, Some("alpha.beta")));
+    assert!(version(1, 0, 0, Some("beta.2")) < version(1, 0, 0, Some("beta.11")));
+    assert!(version(1, 0, 0, Some("rc.1")) > version(1, 0, 0, Some("beta.11")));
+}
+
+#[test]
+fn commit_and_build_are_ignored() {
+    let a = SemanticVersion::new(
+        1,
+        2,
+        3,
+        None,
+        Some("aaaaaaa".into()),
+        Some("aaaaaaa".into()),
+    );
+    let b = SemanticVersion::new(1, 2, 3, None, Some("bbbbbbb".into()), None);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn bump_between_versions() {
+    let from = version(1, 2, 3, None);
+    assert_eq!(
+        bump_between(&from, &version(2, 0, 0, None)),
+        Some(Bump::Major)
+    );
+    assert_eq!(
+        bump_between(&from, &version(1, 3, 0, None)),
+        Some(Bump::Minor)
+    );
+    assert_eq!(
+        bump_between(&from, &version(1, 2, 7, None)),
+        Some(Bump::Patch)
+    );
+    assert_eq!(bump_between(&from, &version(1, 2, 3, None)), None);
+    assert_eq!(bump_between(&from, &version(1, 0, 0, None)), None);
+}
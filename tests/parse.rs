@@ -0,0 +1,192 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{
+    main_version, parse_iso_date, parse_semver, resolve_version, ResolveOptions, SemanticVersion,
+    Separators,
+};
+
+/// Version of a repo whose HEAD carries the given tag
+fn tagged(name: &str, options: &ResolveOptions) -> SemanticVersion {
+    let t = TestRepo::new();
+    let head = t.commit("initial");
+    t.tag(name, head);
+    main_version(&t.repo, options).unwrap()
+}
+
+fn triple(v: &SemanticVersion) -> (usize, usize, usize) {
+    (v.major, v.minor, v.patch)
+}
+
+#[test]
+fn lowercase_v_prefix() {
+    let v = tagged("v1.2.3", &ResolveOptions::default());
+    assert_eq!(triple(&v), (1, 2, 3));
+}
+
+#[test]
+fn uppercase_v_prefix() {
+    let v = tagged("V2.0.0", &ResolveOptions::default());
+    assert_eq!(triple(&v), (2, 0, 0));
+}
+
+#[test]
+fn leading_zero_is_not_a_version() {
+    // the tag is ignored, so HEAD is one commit after nothing
+    let v = tagged("1.02.3", &ResolveOptions::default());
+    assert_eq!(triple(&v), (0, 0, 1));
+}
+
+#[test]
+fn leading_zero_can_be_normalized() {
+    let options = ResolveOptions {
+        leading_zeros: true,
+        ..ResolveOptions::default()
+    };
+    let v = tagged("1.02.3", &options);
+    assert_eq!(triple(&v), (1, 2, 3));
+}
+
+#[test]
+fn malformed_release_commit_is_ignored() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("release: 99999999999999999999999.0.0");
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!(triple(&v), (1, 2, 1));
+}
+
+#[test]
+fn prerelease_ident_round_trips() {
+    let mut v = tagged("2.0.0-beta.2", &ResolveOptions::default());
+    assert_eq!(v.ident.as_deref(), Some("beta.2"));
+    v.build = None;
+    assert_eq!(v.to_string(), "2.0.0-beta.2");
+}
+
+#[test]
+fn prerelease_ident_keeps_case() {
+    let mut v = tagged("v1.2.3-RC1", &ResolveOptions::default());
+    assert_eq!(triple(&v), (1, 2, 3));
+    v.build = None;
+    assert_eq!(v.to_string(), "1.2.3-RC1");
+}
+
+/// Version of a repo with a release commit with the given message
+/// followed by one commit
+fn released(message: &str) -> SemanticVersion {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit(message);
+    t.commit("one");
+    resolve_version(&t.repo).unwrap()
+}
+
+#[test]
+fn release_commit_ignores_trailing_numbers() {
+    let v = released("release: 1.2.3 fixes from ticket 4567");
+    assert_eq!(triple(&v), (1, 2, 4));
+}
+
+#[test]
+fn release_commit_ignores_numbers_in_body() {
+    let v = released("release: 2.0.0\n\nCloses #4567, supersedes 1.9.9");
+    assert_eq!(triple(&v), (2, 0, 1));
+}
+
+#[test]
+fn release_commit_with_version_later_is_not_a_release() {
+    // neither 1.2.3 nor 4567 may be read from prose after the prefix
+    let v = released("release: fixes for 1.2.3 from ticket 4567");
+    assert_eq!(triple(&v), (0, 0, 3));
+}
+
+#[test]
+fn parse_without_repository() {
+    let v = parse_semver("v2.1-rc.1").unwrap();
+    assert_eq!(triple(&v), (2, 1, 0));
+    assert_eq!(v.ident.as_deref(), Some("rc.1"));
+    assert_eq!(v.commit, None);
+
+    assert!(parse_semver("1.02.3").is_none());
+    assert!(parse_semver("latest").is_none());
+}
+
+#[test]
+fn underscore_separator() {
+    let options = ResolveOptions {
+        separators: Separators::new("_").unwrap(),
+        ..ResolveOptions::default()
+    };
+    let mut v = tagged("1_2_3", &options);
+    assert_eq!(triple(&v), (1, 2, 3));
+    v.build = None;
+    assert_eq!(v.to_string(), "1.2.3");
+
+    // only the given separators are accepted
+    let v = tagged("1.2.3", &options);
+    assert_eq!(triple(&v), (0, 0, 1));
+}
+
+#[test]
+fn underscore_is_no_default_separator() {
+    let v = tagged("1_2_3", &ResolveOptions::default());
+    assert_eq!(triple(&v), (0, 0, 1));
+}
+
+#[test]
+fn separators_must_not_be_empty_or_digits() {
+    assert!(Separators::new("").is_err());
+    assert!(Separators::new("1").is_err());
+    assert!(Separators::new("._-").is_ok());
+}
+
+#[test]
+fn from_str_is_strict() {
+    let v: SemanticVersion = "1.2.3-rc.1+build.5".parse().unwrap();
+    assert_eq!(triple(&v), (1, 2, 3));
+    assert_eq!(v.ident.as_deref(), Some("rc.1"));
+    assert_eq!(v.build.as_deref(), Some("build.5"));
+    assert_eq!(v.to_string(), "1.2.3-rc.1+build.5");
+
+    for garbage in [
+        "1.2",
+        "v1.2.3",
+        "1.02.3",
+        "1.2.3-",
+        "1.2.3-rc..1",
+        "1.2.3+",
+        "latest",
+    ] {
+        assert!(garbage.parse::<SemanticVersion>().is_err(), "{}", garbage);
+    }
+    // the lenient parser still takes the short forms
+    assert!(parse_semver("v1.2").is_some());
+}
+
+#[test]
+fn iso_dates() {
+    assert_eq!(parse_iso_date("1970-01-01"), Some(0));
+    assert_eq!(parse_iso_date("2023-11-14"), Some(1_699_920_000));
+    assert_eq!(parse_iso_date("2024-02-29"), Some(1_709_164_800));
+    for garbage in [
+        "2023-02-30",
+        "2023-13-01",
+        "2023-1-01",
+        "23-11-14",
+        "2023-11-14T00:00",
+        "",
+    ] {
+        assert_eq!(parse_iso_date(garbage), None, "{}", garbage);
+    }
+}
+
+#[test]
+fn lenient_parse_takes_short_forms() {
+    assert!(SemanticVersion::parse("v1.2", false).is_err());
+    let v = SemanticVersion::parse("v1.2", true).unwrap();
+    assert_eq!(triple(&v), (1, 2, 0));
+    let v = SemanticVersion::parse("1.2.3-rc.1", false).unwrap();
+    assert_eq!(v.ident.as_deref(), Some("rc.1"));
+    assert!(SemanticVersion::parse("latest", true).is_err());
+}
@@ -0,0 +1,33 @@
+use grelly::SemanticVersion;
+
+fn pep440(ident: Option<&str>, distance: usize) -> String {
+    let mut v = SemanticVersion::new(1, 2, 3, ident.map(String::from), None, None);
+    v.distance = Some(distance);
+    v.pep440_string()
+}
+
+#[test]
+fn release_is_unchanged() {
+    assert_eq!(pep440(None, 0), "1.2.3");
+}
+
+#[test]
+fn distance_is_a_dev_release() {
+    assert_eq!(pep440(None, 4), "1.2.3.dev4");
+}
+
+#[test]
+fn prerelease_idents() {
+    assert_eq!(pep440(Some("rc.1"), 0), "1.2.3rc1");
+    assert_eq!(pep440(Some("RC2"), 0), "1.2.3rc2");
+    assert_eq!(pep440(Some("alpha"), 0), "1.2.3a1");
+    assert_eq!(pep440(Some("beta-3"), 0), "1.2.3b3");
+    assert_eq!(pep440(Some("rc.1"), 2), "1.2.3rc1.dev2");
+}
+
+#[test]
+fn other_idents_are_local_versions() {
+    assert_eq!(pep440(Some("login"), 0), "1.2.3+login");
+    assert_eq!(pep440(Some("Some-Thing"), 5), "1.2.3.dev5+some.thing");
+    assert_eq!(pep440(Some("rc.x"), 0), "1.2.3+rc.x");
+}
@@ -0,0 +1,449 @@
+mod common;
+
+use common::TestRepo;
+use git2::{Signature, Time};
+use grelly::{main_release, Bump, ReleaseOptions, ResolveOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+/// 2023-11-14 22:13:20 UTC
+const RELEASE_TIME: i64 = 1_700_000_000;
+
+fn fixed_signature() -> Signature<'static> {
+    Signature::new(
+        "Release Bot",
+        "bot@example.com",
+        &Time::new(RELEASE_TIME, 0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn date_placeholders_use_the_release_time() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let dir = TempDir::new().unwrap();
+    let template = dir.path().join("changes.tpl");
+    fs::write(&template, "{version} from {date}, (c) {year}\n{commits}\n").unwrap();
+
+    let options = ReleaseOptions {
+        tag_message_format: String::from("Release {major}.{minor}.{patch} on {date}"),
+        changes_template: Some(template),
+        ..ReleaseOptions::default()
+    };
+    let v = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap()
+    .version;
+    assert_eq!(v.to_string(), "1.3.0");
+
+    let tag = t
+        .repo
+        .revparse_single("v1.3.0")
+        .unwrap()
+        .peel_to_tag()
+        .unwrap();
+    assert_eq!(tag.message(), Some("Release 1.3.0 on 2023-11-14"));
+
+    let changes = fs::read_to_string(t.path().join("changes.1.3.0")).unwrap();
+    assert_eq!(changes, "1.3.0 from 2023-11-14, (c) 2023\n- one\n");
+}
+
+#[test]
+fn existing_tag_is_not_replaced_without_force() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let old = t.commit("one");
+    t.tag("v1.3.0", old);
+    t.commit("two");
+
+    // 1.3.1 is released as 1.4.0, whose tag collides with v1.3.0
+    let options = ReleaseOptions {
+        tag_format: String::from("v1.3.{patch}"),
+        ..ReleaseOptions::default()
+    };
+    let err = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("already exists"), "{}", err);
+    assert_eq!(
+        t.repo.head().unwrap().peel_to_commit().unwrap().summary(),
+        Some("two")
+    );
+
+    let options = ReleaseOptions {
+        force: true,
+        ..options
+    };
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+    let tagged = t.repo.revparse_single("v1.3.0^{commit}").unwrap().id();
+    assert_ne!(tagged, old);
+}
+
+#[test]
+fn release_below_an_existing_tag_is_refused() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("2.x");
+    let newer = t.commit("two");
+    t.tag("v2.0.0", newer);
+    t.checkout("master");
+    t.commit("one");
+
+    let err = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &ReleaseOptions::default(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("v2.0.0"), "{}", err);
+
+    let options = ReleaseOptions {
+        allow_downgrade: true,
+        ..ReleaseOptions::default()
+    };
+    let v = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap()
+    .version;
+    assert_eq!(v.to_string(), "1.3.0");
+}
+
+/// Release with the changes file in dir, return the content of the
+/// changes file in the release commit
+fn release_into(t: &TestRepo, dir: PathBuf) -> String {
+    let options = ReleaseOptions {
+        changes_dir: dir,
+        changes_name: String::from("{version}.md"),
+        ..ReleaseOptions::default()
+    };
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+
+    let tree = t.repo.head().unwrap().peel_to_tree().unwrap();
+    let entry = tree.get_path(Path::new("doc/changes/1.3.0.md")).unwrap();
+    let blob = t.repo.find_blob(entry.id()).unwrap();
+    String::from_utf8(blob.content().to_vec()).unwrap()
+}
+
+#[test]
+fn changes_file_in_nested_directory_is_committed() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let content = release_into(&t, "doc/changes".into());
+    assert_eq!(content, "Changes for version 1.3.0\n");
+}
+
+#[test]
+fn absolute_changes_directory_inside_the_workdir() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let dir = t.repo.workdir().unwrap().join("doc/changes");
+    let content = release_into(&t, dir);
+    assert_eq!(content, "Changes for version 1.3.0\n");
+}
+
+#[test]
+fn post_release_hook_sees_version_and_tag() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let dir = TempDir::new().unwrap();
+    let out = dir.path().join("hook.out");
+    let options = ReleaseOptions {
+        post_release_hook: Some(format!(
+            "echo \"$GRELLY_VERSION $GRELLY_TAG\" > '{}'",
+            out.display()
+        )),
+        ..ReleaseOptions::default()
+    };
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "1.3.0 v1.3.0\n");
+}
+
+#[test]
+fn failing_hook_fails_only_if_required() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let options = ReleaseOptions {
+        post_release_hook: Some(String::from("exit 3")),
+        ..ReleaseOptions::default()
+    };
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+
+    t.commit("two");
+    let options = ReleaseOptions {
+        hook_required: true,
+        ..options
+    };
+    assert!(main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options
+    )
+    .is_err());
+}
+
+#[test]
+fn tag_only_tags_head_without_committing() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let head = t.commit("one");
+
+    let options = ReleaseOptions {
+        tag_only: true,
+        ..ReleaseOptions::default()
+    };
+    let v = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap()
+    .version;
+    assert_eq!(v.to_string(), "1.3.0");
+
+    assert_eq!(t.repo.head().unwrap().target(), Some(head));
+    let tagged = t.repo.revparse_single("v1.3.0^{commit}").unwrap().id();
+    assert_eq!(tagged, head);
+    assert!(!t.path().join("changes.1.3.0").exists());
+}
+
+#[test]
+fn inherit_author_of_head() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let options = ReleaseOptions {
+        inherit_author: true,
+        ..ReleaseOptions::default()
+    };
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+
+    let release = t.repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(release.author().name(), Some("Test User"));
+    assert_eq!(release.author().email(), Some("test@example.com"));
+    assert_eq!(release.author().when().seconds(), RELEASE_TIME);
+    let tag = t
+        .repo
+        .revparse_single("v1.3.0")
+        .unwrap()
+        .peel_to_tag()
+        .unwrap();
+    assert_eq!(tag.tagger().unwrap().name(), Some("Test User"));
+}
+
+#[test]
+fn release_on_a_feature_branch_is_refused() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.branch("feature/foo");
+    t.commit("one");
+
+    let err = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &ReleaseOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("feature branch feature/foo"),
+        "{}",
+        err
+    );
+    assert!(t.repo.revparse_single("v1.3.0").is_err());
+
+    let options = ReleaseOptions {
+        allow_any_branch: true,
+        ..ReleaseOptions::default()
+    };
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+}
+
+#[test]
+fn release_on_master_bumps_minor() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let v = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &ReleaseOptions::default(),
+    )
+    .unwrap()
+    .version;
+    assert_eq!(v.to_string(), "1.3.0");
+}
+
+#[test]
+fn release_on_a_release_branch_bumps_patch() {
+    let t = TestRepo::new();
+    let base = t.commit("release: 1.2.0");
+    t.tag("v1.2.0", base);
+    t.branch("release/1.2");
+    t.checkout("master");
+    t.commit("one");
+    main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &ReleaseOptions::default(),
+    )
+    .unwrap();
+
+    // v1.3.0 on master does not block a patch release of 1.2
+    t.checkout("release/1.2");
+    t.commit("fix");
+    let v = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &ReleaseOptions::default(),
+    )
+    .unwrap()
+    .version;
+    assert_eq!(v.to_string(), "1.2.2");
+    assert!(t.repo.revparse_single("v1.2.2").is_ok());
+
+    t.commit("another fix");
+    let options = ReleaseOptions {
+        bump: Some(Bump::Minor),
+        ..ReleaseOptions::default()
+    };
+    let err = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("release branch 1.2"), "{}", err);
+}
+
+#[test]
+fn bump_by_skips_versions() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    t.commit("one");
+
+    let options = ReleaseOptions {
+        bump_by: 3,
+        ..ReleaseOptions::default()
+    };
+    let v = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap()
+    .version;
+    assert_eq!(v.to_string(), "1.5.0");
+
+    t.commit("two");
+    let options = ReleaseOptions {
+        bump: Some(Bump::Patch),
+        bump_by: 0,
+        ..ReleaseOptions::default()
+    };
+    assert!(main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options
+    )
+    .is_err());
+}
+
+#[test]
+fn dry_run_returns_the_plan_and_writes_nothing() {
+    let t = TestRepo::new();
+    t.commit("release: 1.2.0");
+    let head = t.commit("one");
+
+    let options = ReleaseOptions {
+        dry_run: true,
+        ..ReleaseOptions::default()
+    };
+    let release = main_release(
+        &t.repo,
+        &fixed_signature(),
+        &ResolveOptions::default(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(release.version.to_string(), "1.3.0");
+    assert_eq!(release.changes_file, Some(PathBuf::from("changes.1.3.0")));
+    assert_eq!(release.message.as_deref(), Some("release: 1.3.0"));
+    assert_eq!(release.tag, "v1.3.0");
+
+    assert_eq!(t.repo.head().unwrap().target(), Some(head));
+    assert!(t.repo.revparse_single("v1.3.0").is_err());
+    assert!(!t.path().join("changes.1.3.0").exists());
+}
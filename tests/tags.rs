@@ -0,0 +1,273 @@
+mod common;
+
+use common::TestRepo;
+use grelly::{latest_release, main_version, release_history, resolve_version, ResolveOptions};
+use std::time::Instant;
+
+/// A release two commits back, behind 2000 tags on an old commit
+fn many_tags() -> TestRepo {
+    let t = TestRepo::new();
+    let old = t.commit("initial");
+    for i in 0..2000 {
+        t.tag(&format!("build-{}", i), old);
+    }
+    t.commit("one");
+    let release = t.commit("two");
+    t.tag("v3.1.0", release);
+    t.commit("three");
+    t
+}
+
+#[test]
+fn many_tags_do_not_hide_a_near_release() {
+    let t = many_tags();
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (3, 1, 1));
+}
+
+#[test]
+#[ignore = "wall-clock timing, run with --ignored on an idle machine"]
+fn many_tags_do_not_slow_down_a_near_release() {
+    let t = many_tags();
+    let start = Instant::now();
+    resolve_version(&t.repo).unwrap();
+    let elapsed = start.elapsed();
+    assert!(elapsed.as_secs() < 5, "took {:?}", elapsed);
+}
+
+#[test]
+fn lightweight_tag_is_a_release() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    let release = t.commit("one");
+    t.lightweight_tag("v2.4.0", release);
+    t.commit("two");
+    t.commit("three");
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (2, 4, 2));
+}
+
+#[test]
+fn history_lists_every_release_oldest_first() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    let first = t.commit("release: 1.0.0");
+    t.commit("one");
+    let tagged = t.commit("two");
+    t.tag("v1.1.0", tagged);
+    let second = t.commit("release: 2.0.0");
+    t.commit("three");
+
+    let history = release_history(&t.repo).unwrap();
+    let found: Vec<_> = history
+        .iter()
+        .map(|(oid, v)| (*oid, v.to_string()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (first, String::from("1.0.0")),
+            (tagged, String::from("1.1.0")),
+            (second, String::from("2.0.0")),
+        ]
+    );
+}
+
+#[test]
+fn version_from_tag_message() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    let release = t.commit("one");
+    let obj = t.repo.find_object(release, None).unwrap();
+    t.repo
+        .tag(
+            "release-2024",
+            &obj,
+            &t.signature(),
+            "Version 1.4.0\n\nQ1 release",
+            false,
+        )
+        .unwrap();
+    t.commit("two");
+
+    // the name is no version, so by default there is no release
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (0, 0, 3));
+
+    let options = ResolveOptions {
+        tag_message_version: true,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 4, 1));
+}
+
+#[test]
+fn latest_release_ignores_distance() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    let tagged = t.commit("feature");
+    t.tag("v1.4.0-rc.1", tagged);
+    t.commit("one");
+    t.commit("two");
+
+    let options = ResolveOptions::default();
+    let v = latest_release(&t.repo, &options).unwrap().unwrap();
+    assert_eq!(v.to_string(), "1.4.0-rc.1");
+    assert_eq!(main_version(&t.repo, &options).unwrap().patch, 2);
+}
+
+#[test]
+fn latest_release_without_release_is_none() {
+    let t = TestRepo::new();
+    t.commit("initial");
+    t.commit("one");
+    assert!(latest_release(&t.repo, &ResolveOptions::default())
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn max_tag_scan_resolves_the_nearest_tags() {
+    let t = TestRepo::new();
+    let old = t.commit("initial");
+    t.tag("v1.0.0", old);
+    let newer = t.commit("one");
+    t.tag("v2.0.0", newer);
+    let nightly = t.commit("two");
+    t.tag("nightly", nightly);
+    t.commit("three");
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (2, 0, 2));
+
+    // nightly is no version but uses up the only tag
+    let options = ResolveOptions {
+        max_tag_scan: 1,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (0, 0, 4));
+
+    // the nearest release wins, whatever the order of the names
+    let options = ResolveOptions {
+        max_tag_scan: 2,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (2, 0, 2));
+}
+
+#[test]
+fn max_tag_scan_stops_within_a_commit() {
+    let t = TestRepo::new();
+    let release = t.commit("initial");
+    t.tag("a-nightly", release);
+    t.tag("v1.0.0", release);
+    t.commit("one");
+
+    // the limit runs out after the first name of the commit
+    let options = ResolveOptions {
+        max_tag_scan: 1,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (0, 0, 2));
+
+    let options = ResolveOptions {
+        max_tag_scan: 2,
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 0, 1));
+}
+
+#[test]
+fn strict_refuses_a_version_below_a_reachable_tag() {
+    let t = TestRepo::new();
+    let tagged = t.commit("initial");
+    t.tag("v1.3.0", tagged);
+    t.commit("release: 1.1.0");
+    t.commit("one");
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 1, 1));
+
+    let options = ResolveOptions {
+        strict: true,
+        ..ResolveOptions::default()
+    };
+    let err = main_version(&t.repo, &options).unwrap_err();
+    assert!(err.to_string().contains("v1.3.0"), "{}", err);
+
+    t.commit("release: 1.3.1");
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 3, 1));
+}
+
+#[test]
+fn ignored_tags_are_no_releases() {
+    let t = TestRepo::new();
+    let release = t.commit("initial");
+    t.tag("v1.2.0", release);
+    let test = t.commit("one");
+    t.tag("v0.0.0-test", test);
+    let ci = t.commit("two");
+    t.tag("ci-2.0.0", ci);
+    t.commit("three");
+
+    let options = ResolveOptions {
+        ignore_tags: vec![String::from("ci-*"), String::from("v?.?.?-test")],
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 2, 3));
+
+    let options = ResolveOptions {
+        ignore_tags: vec![String::from("ci-*")],
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (0, 0, 2));
+}
+
+#[test]
+fn tags_to_missing_objects_are_dropped_or_required() {
+    let t = TestRepo::new();
+    let release = t.commit("initial");
+    t.tag("v1.2.0", release);
+    t.commit("one");
+    // as left behind by a shallow fetch, the ref without its object
+    std::fs::write(
+        t.repo.path().join("refs/tags/v2.0.0"),
+        "1234567890123456789012345678901234567890\n",
+    )
+    .unwrap();
+
+    let v = resolve_version(&t.repo).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 2, 1));
+
+    let options = ResolveOptions {
+        require_tags: true,
+        ..ResolveOptions::default()
+    };
+    let err = main_version(&t.repo, &options).unwrap_err();
+    assert!(err.to_string().contains("v2.0.0"), "{}", err);
+}
+
+#[test]
+fn from_tag_honours_tag_prefix() {
+    let t = TestRepo::new();
+    let release = t.commit("initial");
+    t.tag("rel-1.2.0", release);
+    t.commit("one");
+
+    let options = ResolveOptions {
+        tag_prefix: Some(String::from("rel-")),
+        from_tag: Some(String::from("rel-1.2.0")),
+        ..ResolveOptions::default()
+    };
+    let v = main_version(&t.repo, &options).unwrap();
+    assert_eq!((v.major, v.minor, v.patch), (1, 2, 1));
+}
@@ -0,0 +1,61 @@
+use grelly::{render_output, SemanticVersion};
+
+fn version() -> SemanticVersion {
+    SemanticVersion::new(
+        1,
+        2,
+        3,
+        Some(String::from("foo")),
+        Some(String::from("a1b2c3d")),
+        Some(String::from("a1b2c3d")),
+    )
+}
+
+fn render(template: &str) -> String {
+    render_output(template, &version(), "feature/foo").unwrap()
+}
+
+#[test]
+fn each_placeholder() {
+    assert_eq!(render("{major}"), "1");
+    assert_eq!(render("{minor}"), "2");
+    assert_eq!(render("{patch}"), "3");
+    assert_eq!(render("{ident}"), "foo");
+    assert_eq!(render("{commit}"), "a1b2c3d");
+    assert_eq!(render("{branch}"), "feature/foo");
+}
+
+#[test]
+fn literal_text_around_placeholders() {
+    assert_eq!(
+        render("build-{major}.{minor}-{commit}"),
+        "build-1.2-a1b2c3d"
+    );
+    assert_eq!(render("no placeholders"), "no placeholders");
+    assert_eq!(render(""), "");
+}
+
+#[test]
+fn missing_parts_are_empty() {
+    let v = SemanticVersion::new(1, 2, 3, None, None, None);
+    let out = render_output("{major}-{ident}-{commit}", &v, "").unwrap();
+    assert_eq!(out, "1--");
+}
+
+#[test]
+fn escaped_braces() {
+    assert_eq!(render("{{major}}"), "{major}");
+    assert_eq!(render("{{{major}}}"), "{1}");
+    assert_eq!(render("}}{{"), "}{");
+}
+
+#[test]
+fn bad_templates_are_errors() {
+    for bad in ["{nope}", "{major", "major}", "{", "}", "{{major}"] {
+        assert!(
+            render_output(bad, &version(), "").is_err(),
+            "{} rendered",
+            bad
+        );
+    }
+}